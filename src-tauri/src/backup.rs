@@ -0,0 +1,287 @@
+use crate::cover::{CUSTOM_COVER_DIR, GRID_THUMBNAIL_FILE, LIST_THUMBNAIL_FILE};
+use crate::file::get_current_timestamp;
+use crate::jobs::{spawn_job, JobHandle};
+use crate::library::get_books_dir;
+use crate::model::{BackupFileEntry, BackupManifest, JobKind};
+use crate::navigation::NAVIGATION_FILE;
+use crate::progress::PROGRESS_FILE;
+use crate::session::SESSIONS_FILE;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+use tauri::Manager;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+// 备份格式版本
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+// 备份中的清单文件名
+pub const BACKUP_MANIFEST_FILE: &str = "manifest.json";
+// 备份的配置文件（监视文件夹与本机路径相关，书库索引可以重新生成，不备份）
+pub const BACKUP_CONFIG_FILES: [&str; 4] = [
+    "reader_style.json",
+    "collections.json",
+    "smart_shelves.json",
+    "txt_import.json",
+];
+// 只备份笔记时包含的书籍文件
+const NOTE_FILES: [&str; 5] = [
+    "mark.json",
+    PROGRESS_FILE,
+    SESSIONS_FILE,
+    "metadata.json",
+    ".lastopened",
+];
+
+// 判断书籍目录中的文件是否需要备份，relative 为相对于 books/ 的路径
+// 正在复制的 .part 文件、缩略图和目录缓存可以重新生成，不备份
+// 只备份笔记时只包含书签、阅读进度等文件和自定义封面
+fn should_back_up(relative: &Path, include_books: bool) -> bool {
+    let parts: Vec<String> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    let Some(file_name) = parts.last() else {
+        return false;
+    };
+    if file_name.ends_with(".part")
+        || [GRID_THUMBNAIL_FILE, LIST_THUMBNAIL_FILE, NAVIGATION_FILE].contains(&file_name.as_str())
+    {
+        return false;
+    }
+    if include_books {
+        return true;
+    }
+    match parts.as_slice() {
+        [_, file] => NOTE_FILES.contains(&file.as_str()),
+        [_, dir, _] => dir == CUSTOM_COVER_DIR,
+        _ => false,
+    }
+}
+
+// 递归收集目录下的所有文件
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    for entry in fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read directory {}: {}", dir.display(), e))?
+    {
+        let path = entry
+            .map_err(|e| format!("Failed to read directory entry: {}", e))?
+            .path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+// 计算文件在备份中的路径，统一使用 / 作为分隔符
+fn archive_path(base_dir: &Path, path: &Path, prefix: &str) -> Option<String> {
+    let relative = path.strip_prefix(base_dir).ok()?;
+    let parts: Vec<String> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect();
+    Some(format!("{}/{}", prefix, parts.join("/")))
+}
+
+// 将文件写入压缩包，同时计算 SHA-256 用于恢复时校验
+fn write_file_to_archive(
+    zip: &mut ZipWriter<File>,
+    source: &Path,
+    name: &str,
+) -> Result<BackupFileEntry, String> {
+    // epub和图片本身已经压缩过，直接存储
+    let compressed = !matches!(
+        source
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .as_deref(),
+        Some("epub" | "jpg" | "jpeg" | "png" | "gif" | "webp")
+    );
+    let options = FileOptions::default()
+        .compression_method(if compressed {
+            CompressionMethod::Deflated
+        } else {
+            CompressionMethod::Stored
+        })
+        .large_file(true);
+    zip.start_file(name, options)
+        .map_err(|e| format!("Failed to add {} to archive: {}", name, e))?;
+
+    let mut file =
+        File::open(source).map_err(|e| format!("Failed to open {}: {}", source.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        zip.write_all(&buffer[..read])
+            .map_err(|e| format!("Failed to write {} to archive: {}", name, e))?;
+        size += read as u64;
+    }
+
+    Ok(BackupFileEntry {
+        path: name.to_string(),
+        size,
+        sha256: format!("{:x}", hasher.finalize()),
+    })
+}
+
+// 导出书库备份
+// include_books 为 false 时不包含epub文件，只备份书签、阅读进度、元数据和自定义封面等笔记数据
+fn export_library(
+    app_dir: &Path,
+    books_dir: &Path,
+    dest_path: &Path,
+    include_books: bool,
+    job: &JobHandle,
+) -> Result<BackupManifest, String> {
+    let mut sources: Vec<(PathBuf, String)> = Vec::new();
+
+    if books_dir.exists() {
+        let mut files = Vec::new();
+        collect_files(books_dir, &mut files)?;
+        files.sort();
+        for file in files {
+            let relative = file.strip_prefix(books_dir).unwrap_or(&file);
+            if !should_back_up(relative, include_books) {
+                continue;
+            }
+            if let Some(name) = archive_path(books_dir, &file, "books") {
+                sources.push((file, name));
+            }
+        }
+    }
+    let config_dir = app_dir.join("config");
+    for config_file in BACKUP_CONFIG_FILES {
+        let path = config_dir.join(config_file);
+        if path.exists() {
+            sources.push((path, format!("config/{}", config_file)));
+        }
+    }
+    job.set_total(sources.len());
+
+    // 先写到目标旁边的临时文件，完成后再替换，失败或取消时不会留下不完整的压缩包
+    let file_name = dest_path
+        .file_name()
+        .ok_or_else(|| format!("Invalid backup path: {}", dest_path.display()))?;
+    let temp_path = dest_path.with_file_name(format!(".{}.part", file_name.to_string_lossy()));
+    let result =
+        write_backup_archive(&temp_path, sources, include_books, job).and_then(|manifest| {
+            fs::rename(&temp_path, dest_path)
+                .map_err(|e| format!("Failed to save backup archive: {}", e))?;
+            Ok(manifest)
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+// 将文件写入备份压缩包，最后写入清单
+fn write_backup_archive(
+    archive_path: &Path,
+    sources: Vec<(PathBuf, String)>,
+    include_books: bool,
+    job: &JobHandle,
+) -> Result<BackupManifest, String> {
+    let file = File::create(archive_path)
+        .map_err(|e| format!("Failed to create backup archive: {}", e))?;
+    let mut zip = ZipWriter::new(file);
+    let mut entries = Vec::new();
+    for (source, name) in sources {
+        if job.is_cancelled() {
+            return Err("Cancelled".to_string());
+        }
+        entries.push(write_file_to_archive(&mut zip, &source, &name)?);
+        job.advance(Some(name));
+    }
+
+    let manifest = BackupManifest {
+        format_version: BACKUP_FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: get_current_timestamp()?,
+        include_books,
+        files: entries,
+    };
+    let json_data = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| format!("Failed to serialize backup manifest: {}", e))?;
+    zip.start_file(BACKUP_MANIFEST_FILE, FileOptions::default())
+        .map_err(|e| format!("Failed to add manifest to archive: {}", e))?;
+    zip.write_all(json_data.as_bytes())
+        .map_err(|e| format!("Failed to write manifest to archive: {}", e))?;
+    zip.finish()
+        .and_then(|file| file.sync_all().map_err(Into::into))
+        .map_err(|e| format!("Failed to finish backup archive: {}", e))?;
+
+    Ok(manifest)
+}
+
+// 提交后台导出任务，返回任务ID，完成后任务结果为备份清单
+pub fn start_export_job(
+    app_handle: &AppHandle,
+    dest_path: &str,
+    include_books: bool,
+) -> Result<String, String> {
+    let app_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+    let books_dir = get_books_dir(app_handle)?;
+    let dest_path = PathBuf::from(dest_path);
+
+    spawn_job(app_handle, JobKind::Export, move |job| async move {
+        let manifest = tokio::task::spawn_blocking(move || {
+            export_library(&app_dir, &books_dir, &dest_path, include_books, &job)
+        })
+        .await
+        .map_err(|e| format!("Export task failed: {}", e))??;
+        serde_json::to_value(manifest).map_err(|e| format!("Failed to serialize manifest: {}", e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caches_and_partial_files_are_never_backed_up() {
+        for path in [
+            "abc/book.epub.part",
+            "abc/thumb_grid.jpg",
+            "abc/custom_cover/thumb_list.jpg",
+            "abc/navigation.json",
+        ] {
+            assert!(!should_back_up(Path::new(path), true), "{}", path);
+        }
+        assert!(should_back_up(Path::new("abc/book.epub"), true));
+        assert!(should_back_up(Path::new("abc/cover.png"), true));
+    }
+
+    #[test]
+    fn notes_only_backup_keeps_note_files_and_custom_cover() {
+        for path in [
+            "abc/mark.json",
+            "abc/progress.json",
+            "abc/sessions.json",
+            "abc/metadata.json",
+            "abc/.lastopened",
+            "abc/custom_cover/cover.jpg",
+        ] {
+            assert!(should_back_up(Path::new(path), false), "{}", path);
+        }
+        for path in ["abc/book.epub", "abc/cover.jpg", "abc/notes/mark.json"] {
+            assert!(!should_back_up(Path::new(path), false), "{}", path);
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use crate::file::get_current_timestamp;
+use crate::library::{epub_file_from_record, get_books_dir, reconcile_library_index};
+use crate::model::{Collection, CollectionStore, EpubFile};
+use rand::Rng;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::AppHandle;
+use tauri::Manager;
+
+// 书架配置的读写锁
+pub(crate) static COLLECTION_LOCK: Mutex<()> = Mutex::new(());
+
+// 书架配置文件路径 /com.rbook.app/config/collections.json
+fn get_collections_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Unable to get app data directory: {}", e))?;
+    Ok(app_dir.join("config").join("collections.json"))
+}
+
+// 读取所有书架，文件不存在时返回空列表
+fn read_collections(app_handle: &AppHandle) -> Result<CollectionStore, String> {
+    let collections_path = get_collections_path(app_handle)?;
+    if !collections_path.exists() {
+        return Ok(CollectionStore::default());
+    }
+    let json_data = fs::read_to_string(&collections_path)
+        .map_err(|e| format!("Failed to read collections file: {}", e))?;
+    serde_json::from_str(&json_data)
+        .map_err(|e| format!("Failed to deserialize collections: {}", e))
+}
+
+// 保存所有书架
+fn write_collections(app_handle: &AppHandle, store: &CollectionStore) -> Result<(), String> {
+    let collections_path = get_collections_path(app_handle)?;
+    if let Some(config_dir) = collections_path.parent() {
+        if !config_dir.exists() {
+            fs::create_dir_all(config_dir)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+    }
+    let json_data = serde_json::to_string(store)
+        .map_err(|e| format!("Failed to serialize collections: {}", e))?;
+    fs::write(&collections_path, json_data)
+        .map_err(|e| format!("Failed to write collections to file: {}", e))
+}
+
+// 在锁内读取、修改并保存书架
+fn update_collections<T>(
+    app_handle: &AppHandle,
+    f: impl FnOnce(&mut CollectionStore) -> Result<T, String>,
+) -> Result<T, String> {
+    let _guard = COLLECTION_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut store = read_collections(app_handle)?;
+    let result = f(&mut store)?;
+    write_collections(app_handle, &store)?;
+    Ok(result)
+}
+
+// 查找指定ID的书架
+fn find_collection<'a>(
+    store: &'a mut CollectionStore,
+    collection_id: &str,
+) -> Result<&'a mut Collection, String> {
+    store
+        .collections
+        .iter_mut()
+        .find(|c| c.id == collection_id)
+        .ok_or_else(|| format!("Collection not found: {}", collection_id))
+}
+
+// 生成随机ID（书架、智能书架使用）
+pub fn generate_id() -> String {
+    format!("{:016x}", rand::thread_rng().gen::<u64>())
+}
+
+// 获取所有书架
+pub async fn load_collections(app_handle: &AppHandle) -> Result<Vec<Collection>, String> {
+    let _guard = COLLECTION_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(read_collections(app_handle)?.collections)
+}
+
+// 新建书架
+pub async fn create_collection(app_handle: &AppHandle, name: &str) -> Result<Collection, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Collection name must not be empty".to_string());
+    }
+    let now = get_current_timestamp()?;
+    update_collections(app_handle, |store| {
+        let collection = Collection {
+            id: generate_id(),
+            name: name.to_string(),
+            book_ids: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        };
+        store.collections.push(collection.clone());
+        Ok(collection)
+    })
+}
+
+// 重命名书架
+pub async fn rename_collection(
+    app_handle: &AppHandle,
+    collection_id: &str,
+    name: &str,
+) -> Result<Collection, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Collection name must not be empty".to_string());
+    }
+    let now = get_current_timestamp()?;
+    update_collections(app_handle, |store| {
+        let collection = find_collection(store, collection_id)?;
+        collection.name = name.to_string();
+        collection.updated_at = now;
+        Ok(collection.clone())
+    })
+}
+
+// 删除书架，书架中的书籍不受影响
+pub async fn delete_collection(app_handle: &AppHandle, collection_id: &str) -> Result<(), String> {
+    update_collections(app_handle, |store| {
+        let count = store.collections.len();
+        store.collections.retain(|c| c.id != collection_id);
+        if store.collections.len() == count {
+            return Err(format!("Collection not found: {}", collection_id));
+        }
+        Ok(())
+    })
+}
+
+// 将书籍加入书架，position为空时追加到末尾；书籍已在书架中时移动到指定位置
+pub async fn add_book_to_collection(
+    app_handle: &AppHandle,
+    collection_id: &str,
+    book_id: &str,
+    position: Option<usize>,
+) -> Result<Collection, String> {
+    let now = get_current_timestamp()?;
+    update_collections(app_handle, |store| {
+        let collection = find_collection(store, collection_id)?;
+        collection.book_ids.retain(|id| id != book_id);
+        let position = position
+            .unwrap_or(collection.book_ids.len())
+            .min(collection.book_ids.len());
+        collection.book_ids.insert(position, book_id.to_string());
+        collection.updated_at = now;
+        Ok(collection.clone())
+    })
+}
+
+// 将书籍移出书架
+pub async fn remove_book_from_collection(
+    app_handle: &AppHandle,
+    collection_id: &str,
+    book_id: &str,
+) -> Result<Collection, String> {
+    let now = get_current_timestamp()?;
+    update_collections(app_handle, |store| {
+        let collection = find_collection(store, collection_id)?;
+        collection.book_ids.retain(|id| id != book_id);
+        collection.updated_at = now;
+        Ok(collection.clone())
+    })
+}
+
+// 将所有书架中的书籍替换为另一本（合并重复书籍时调用），保留原来的位置
+pub fn replace_book_in_collections(
+    app_handle: &AppHandle,
+    old_id: &str,
+    new_id: &str,
+) -> Result<(), String> {
+    let now = get_current_timestamp()?;
+    update_collections(app_handle, |store| {
+        for collection in store.collections.iter_mut() {
+            let Some(position) = collection.book_ids.iter().position(|id| id == old_id) else {
+                continue;
+            };
+            if collection.book_ids.iter().any(|id| id == new_id) {
+                collection.book_ids.remove(position);
+            } else {
+                collection.book_ids[position] = new_id.to_string();
+            }
+            collection.updated_at = now;
+        }
+        Ok(())
+    })
+}
+
+// 从所有书架中移除一本书（书籍被彻底删除时调用）
+pub fn remove_book_from_collections(app_handle: &AppHandle, book_id: &str) -> Result<(), String> {
+    let now = get_current_timestamp()?;
+    update_collections(app_handle, |store| {
+        for collection in store.collections.iter_mut() {
+            if collection.book_ids.iter().any(|id| id == book_id) {
+                collection.book_ids.retain(|id| id != book_id);
+                collection.updated_at = now;
+            }
+        }
+        Ok(())
+    })
+}
+
+// 重新排列书架中的书籍，book_ids必须与书架中已有的书籍一致
+pub async fn reorder_collection(
+    app_handle: &AppHandle,
+    collection_id: &str,
+    book_ids: Vec<String>,
+) -> Result<Collection, String> {
+    let now = get_current_timestamp()?;
+    update_collections(app_handle, |store| {
+        let collection = find_collection(store, collection_id)?;
+        let mut current = collection.book_ids.clone();
+        let mut requested = book_ids.clone();
+        current.sort();
+        requested.sort();
+        if current != requested {
+            return Err("Book list does not match the collection".to_string());
+        }
+        collection.book_ids = book_ids;
+        collection.updated_at = now;
+        Ok(collection.clone())
+    })
+}
+
+// 获取书架中的书籍，按书架中的顺序返回
+// 已删除（或在回收站中）的书籍会被跳过，恢复后重新出现在书架中
+pub async fn load_collection_books(
+    app_handle: &AppHandle,
+    collection_id: &str,
+) -> Result<Vec<EpubFile>, String> {
+    let book_ids = {
+        let _guard = COLLECTION_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut store = read_collections(app_handle)?;
+        find_collection(&mut store, collection_id)?.book_ids.clone()
+    };
+
+    let books_dir = get_books_dir(app_handle)?;
+    let index = reconcile_library_index(app_handle).await?;
+    Ok(book_ids
+        .iter()
+        .filter_map(|id| index.books.iter().find(|r| &r.id == id))
+        .map(|record| epub_file_from_record(&books_dir, record))
+        .collect())
+}
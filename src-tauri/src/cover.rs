@@ -1,35 +1,198 @@
-use tauri::AppHandle;
-use tauri::Manager;
-use std::path::Path;
-use std::fs;
-
-// 使用默认封面图片
-pub fn use_default_cover() -> Result<Vec<u8>, String> {
-    let default_cover_path = Path::new("resources/default_cover.png");
-    if default_cover_path.exists() {
-        let file_content = fs::read(&default_cover_path)
-            .map_err(|e| format!("Failed to read default cover file: {}", e))?;
-        Ok(file_content)
+use crate::file::{find_epub_in_dir, read_epub_cover};
+use image::ImageFormat;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// 封面格式版本，旧版本的封面统一保存为cover.jpg且没有缩略图
+// 低于该版本的索引记录会按图片内容修正封面的扩展名，缩略图在第一次请求时生成
+pub const COVER_VERSION: u32 = 2;
+// 书库网格视图使用的缩略图
+pub const GRID_THUMBNAIL_FILE: &str = "thumb_grid.jpg";
+// 书库列表视图使用的缩略图
+pub const LIST_THUMBNAIL_FILE: &str = "thumb_list.jpg";
+const GRID_THUMBNAIL_SIZE: (u32, u32) = (300, 450);
+const LIST_THUMBNAIL_SIZE: (u32, u32) = (80, 120);
+// 用户自定义封面所在的子目录，提取出的封面保留在书籍目录中以便恢复
+pub const CUSTOM_COVER_DIR: &str = "custom_cover";
+// 支持的封面文件扩展名
+const COVER_EXTENSIONS: [&str; 6] = ["jpg", "png", "gif", "webp", "bmp", "svg"];
+
+// 根据图片内容（优先）或MIME类型确定封面文件的扩展名
+fn cover_extension(data: &[u8], mime_type: &str) -> &'static str {
+    match image::guess_format(data) {
+        Ok(ImageFormat::Jpeg) => "jpg",
+        Ok(ImageFormat::Png) => "png",
+        Ok(ImageFormat::Gif) => "gif",
+        Ok(ImageFormat::WebP) => "webp",
+        Ok(ImageFormat::Bmp) => "bmp",
+        _ => match mime_type {
+            "image/png" => "png",
+            "image/gif" => "gif",
+            "image/webp" => "webp",
+            "image/bmp" => "bmp",
+            "image/svg+xml" => "svg",
+            _ => "jpg",
+        },
+    }
+}
+
+// 查找书籍目录中的封面文件 cover.*
+pub fn find_cover_file(dir: &Path) -> Option<String> {
+    COVER_EXTENSIONS
+        .iter()
+        .map(|ext| format!("cover.{}", ext))
+        .find(|name| dir.join(name).exists())
+}
+
+// 旧版本把所有格式的封面（包括生成的PNG封面）都保存为 cover.jpg
+// 按文件头判断真实格式并改为对应的扩展名，返回修正后的封面文件名
+pub fn fix_cover_extension(dir: &Path, cover_file: &str) -> String {
+    if cover_file != "cover.jpg" {
+        return cover_file.to_string();
+    }
+    let path = dir.join(cover_file);
+    let mut header = Vec::with_capacity(64);
+    if File::open(&path)
+        .and_then(|file| file.take(64).read_to_end(&mut header))
+        .is_err()
+    {
+        return cover_file.to_string();
+    }
+    let extension = if header.trim_ascii_start().starts_with(b"<") {
+        "svg"
     } else {
-        Err("Default cover image not found".to_string())
+        cover_extension(&header, "image/jpeg")
+    };
+    if extension == "jpg" {
+        return cover_file.to_string();
+    }
+    let fixed = format!("cover.{}", extension);
+    match fs::rename(&path, dir.join(&fixed)) {
+        Ok(()) => fixed,
+        Err(e) => {
+            println!("Failed to rename {}: {}", path.display(), e);
+            cover_file.to_string()
+        }
     }
 }
 
-// 将默认封面图片复制到应用程序目录 初始化
-pub fn init_default_cover(app_handle: &AppHandle, resource_path: &str) -> Result<(), String> {
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("unable to get dir: {}", e))?;
-    let cover_dir = app_dir.join("cover");
-    if !cover_dir.exists() {
-        std::fs::create_dir_all(&cover_dir)
-            .map_err(|e| format!("Failed to create cover directory: {}", e))?;
+// 缩略图路径，缩略图不存在时（例如无法解码的SVG封面）使用封面原图
+fn thumbnail_path(dir: &Path, thumbnail_file: &str, cover_file: &str) -> PathBuf {
+    let path = dir.join(thumbnail_file);
+    if path.exists() {
+        path
+    } else {
+        dir.join(cover_file)
     }
-    let default_cover_path = cover_dir.join("default_cover.png");
-    if !default_cover_path.exists() {
-        std::fs::copy(resource_path, default_cover_path)
-            .map_err(|e| format!("Failed to copy default cover: {}", e))?;
+}
+
+// 请求的封面尺寸
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverSize {
+    Grid,
+    List,
+    Full,
+}
+
+// 获取书籍封面图片的路径，优先使用自定义封面
+// 缩略图在第一次请求时才生成，旧版书库升级后不需要一次性重新生成所有封面
+// 封面文件被删除时从epub中重新提取
+pub fn ensure_cover_image(book_dir: &Path, size: CoverSize) -> Result<PathBuf, String> {
+    let custom_dir = book_dir.join(CUSTOM_COVER_DIR);
+    let (dir, cover_file) = match find_cover_file(&custom_dir) {
+        Some(cover_file) => (custom_dir, cover_file),
+        None => match find_cover_file(book_dir) {
+            Some(cover_file) => (book_dir.to_path_buf(), cover_file),
+            None => {
+                let epub_path = find_epub_in_dir(book_dir)
+                    .ok_or_else(|| format!("No epub file found in {}", book_dir.display()))?;
+                let cover_file = read_epub_cover(book_dir, &epub_path)?;
+                (book_dir.to_path_buf(), cover_file)
+            }
+        },
+    };
+
+    let thumbnail_file = match size {
+        CoverSize::Grid => GRID_THUMBNAIL_FILE,
+        CoverSize::List => LIST_THUMBNAIL_FILE,
+        // 原图按扩展名确定MIME类型，旧版本以错误扩展名保存的封面先修正
+        CoverSize::Full => return Ok(dir.join(fix_cover_extension(&dir, &cover_file))),
+    };
+    if !dir.join(thumbnail_file).exists() {
+        let data = fs::read(dir.join(&cover_file))
+            .map_err(|e| format!("Failed to read cover file: {}", e))?;
+        if let Err(e) = generate_thumbnails(&dir, &data) {
+            // 无法解码时使用原图
+            println!("Failed to generate thumbnails in {}: {}", dir.display(), e);
+        }
+    }
+    Ok(thumbnail_path(&dir, thumbnail_file, &cover_file))
+}
+
+// 删除书籍目录中已有的封面和缩略图
+fn remove_cover_files(dir: &Path) {
+    for ext in COVER_EXTENSIONS {
+        let _ = fs::remove_file(dir.join(format!("cover.{}", ext)));
+    }
+    let _ = fs::remove_file(dir.join(GRID_THUMBNAIL_FILE));
+    let _ = fs::remove_file(dir.join(LIST_THUMBNAIL_FILE));
+}
+
+// 生成网格和列表两种尺寸的缩略图
+pub fn generate_thumbnails(dir: &Path, data: &[u8]) -> Result<(), String> {
+    let image =
+        image::load_from_memory(data).map_err(|e| format!("Failed to decode cover: {}", e))?;
+    for (file, (width, height)) in [
+        (GRID_THUMBNAIL_FILE, GRID_THUMBNAIL_SIZE),
+        (LIST_THUMBNAIL_FILE, LIST_THUMBNAIL_SIZE),
+    ] {
+        // JPEG 不支持透明通道，统一转换为 RGB
+        let thumbnail = image.thumbnail(width, height).to_rgb8();
+        thumbnail
+            .save_with_format(dir.join(file), ImageFormat::Jpeg)
+            .map_err(|e| format!("Failed to save thumbnail: {}", e))?;
     }
     Ok(())
 }
+
+// 按真实格式保存封面原图并生成缩略图，返回封面文件名
+pub fn save_cover(dir: &Path, data: &[u8], mime_type: &str) -> Result<String, String> {
+    remove_cover_files(dir);
+    let cover_file = format!("cover.{}", cover_extension(data, mime_type));
+    fs::write(dir.join(&cover_file), data)
+        .map_err(|e| format!("Failed to write cover file: {}", e))?;
+    if let Err(e) = generate_thumbnails(dir, data) {
+        // 无法解码时只保留原图，前端直接使用原图
+        println!("Failed to generate thumbnails in {}: {}", dir.display(), e);
+    }
+    Ok(cover_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_png_cover_gets_png_extension() {
+        let dir = std::env::temp_dir().join(format!("rbook-cover-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("cover.jpg"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+
+        assert_eq!(fix_cover_extension(&dir, "cover.jpg"), "cover.png");
+        assert!(dir.join("cover.png").exists());
+        assert!(!dir.join("cover.jpg").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn jpeg_cover_keeps_its_name() {
+        let dir = std::env::temp_dir().join(format!("rbook-cover-jpeg-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("cover.jpg"), b"\xff\xd8\xff\xe0\0\x10JFIF").unwrap();
+
+        assert_eq!(fix_cover_extension(&dir, "cover.jpg"), "cover.jpg");
+        assert_eq!(fix_cover_extension(&dir, "cover.gif"), "cover.gif");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,127 @@
+use crate::cover::{save_cover, CUSTOM_COVER_DIR};
+use crate::file::find_epub_in_dir;
+use crate::library::{
+    build_library_record, epub_file_from_record, get_book_dir, upsert_library_record,
+};
+use crate::model::{EpubFile, EpubImage};
+use epub::doc::EpubDoc;
+use std::fs;
+use std::path::Path;
+use tauri::AppHandle;
+
+// 检查数据是否为可用的图片（可解码的位图或SVG）
+fn validate_image(data: &[u8], mime_type: &str) -> Result<(), String> {
+    if mime_type == "image/svg+xml" || image::load_from_memory(data).is_ok() {
+        Ok(())
+    } else {
+        Err("Unsupported or corrupt image".to_string())
+    }
+}
+
+// 保存自定义封面并更新书库索引
+fn apply_custom_cover(
+    app_handle: &AppHandle,
+    book_dir: &Path,
+    data: &[u8],
+    mime_type: &str,
+) -> Result<EpubFile, String> {
+    validate_image(data, mime_type)?;
+    let custom_dir = book_dir.join(CUSTOM_COVER_DIR);
+    if !custom_dir.exists() {
+        fs::create_dir_all(&custom_dir)
+            .map_err(|e| format!("Failed to create custom cover directory: {}", e))?;
+    }
+    save_cover(&custom_dir, data, mime_type)?;
+    refresh_record(app_handle, book_dir)
+}
+
+// 重新生成书籍的索引记录
+fn refresh_record(app_handle: &AppHandle, book_dir: &Path) -> Result<EpubFile, String> {
+    let epub_path = find_epub_in_dir(book_dir)
+        .ok_or_else(|| format!("No epub file found in {}", book_dir.display()))?;
+    let record = build_library_record(book_dir, &epub_path)?;
+    upsert_library_record(app_handle, record.clone())?;
+    let books_dir = book_dir.parent().ok_or("Failed to get books directory")?;
+    Ok(epub_file_from_record(books_dir, &record))
+}
+
+// 打开书籍目录中的epub文件
+fn open_book(book_dir: &Path) -> Result<EpubDoc<std::io::BufReader<fs::File>>, String> {
+    let epub_path = find_epub_in_dir(book_dir)
+        .ok_or_else(|| format!("No epub file found in {}", book_dir.display()))?;
+    EpubDoc::new(epub_path).map_err(|e| e.to_string())
+}
+
+// 使用用户选择的图片文件作为书籍封面
+pub async fn set_custom_cover_from_file(
+    app_handle: &AppHandle,
+    book_id: &str,
+    image_path: &str,
+) -> Result<EpubFile, String> {
+    let book_dir = get_book_dir(app_handle, book_id)?;
+    if !book_dir.exists() {
+        return Err(format!("Book not found: {}", book_id));
+    }
+    let data = fs::read(image_path).map_err(|e| format!("Failed to read image file: {}", e))?;
+    let mime_type = match Path::new(image_path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .as_deref()
+    {
+        Some("svg") => "image/svg+xml",
+        _ => "",
+    };
+    apply_custom_cover(app_handle, &book_dir, &data, mime_type)
+}
+
+// 列出epub清单中的所有图片，供用户选择封面
+pub async fn list_epub_images(
+    app_handle: &AppHandle,
+    book_id: &str,
+) -> Result<Vec<EpubImage>, String> {
+    let book_dir = get_book_dir(app_handle, book_id)?;
+    let doc = open_book(&book_dir)?;
+    let mut images: Vec<EpubImage> = doc
+        .resources
+        .iter()
+        .filter(|(_, (_, mime))| mime.starts_with("image/"))
+        .map(|(id, (path, mime))| EpubImage {
+            id: id.clone(),
+            path: path.to_string_lossy().replace('\\', "/"),
+            mime: mime.clone(),
+        })
+        .collect();
+    images.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(images)
+}
+
+// 使用epub清单中的图片作为书籍封面，resource_id 为清单中的项目ID
+pub async fn set_custom_cover_from_epub(
+    app_handle: &AppHandle,
+    book_id: &str,
+    resource_id: &str,
+) -> Result<EpubFile, String> {
+    let book_dir = get_book_dir(app_handle, book_id)?;
+    let mut doc = open_book(&book_dir)?;
+    let (data, mime_type) = doc
+        .get_resource(resource_id)
+        .ok_or_else(|| format!("Resource not found: {}", resource_id))?;
+    if !mime_type.starts_with("image/") {
+        return Err(format!("Resource is not an image: {}", resource_id));
+    }
+    apply_custom_cover(app_handle, &book_dir, &data, &mime_type)
+}
+
+// 删除自定义封面，恢复使用从epub中提取的封面
+pub async fn revert_custom_cover(
+    app_handle: &AppHandle,
+    book_id: &str,
+) -> Result<EpubFile, String> {
+    let book_dir = get_book_dir(app_handle, book_id)?;
+    let custom_dir = book_dir.join(CUSTOM_COVER_DIR);
+    if custom_dir.exists() {
+        fs::remove_dir_all(&custom_dir)
+            .map_err(|e| format!("Failed to remove custom cover: {}", e))?;
+    }
+    refresh_record(app_handle, &book_dir)
+}
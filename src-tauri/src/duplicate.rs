@@ -0,0 +1,357 @@
+use crate::collection::replace_book_in_collections;
+use crate::file::get_last_opened;
+use crate::library::{epub_file_from_record, get_book_dir, get_books_dir, reconcile_library_index};
+use crate::model::{
+    BookMark, DuplicateGroup, DuplicateReason, EpubFile, LibraryRecord, ReadingProgress,
+};
+use crate::progress::{read_progress, write_progress};
+use crate::session::{read_sessions, write_sessions};
+use crate::trash::delete_book;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+// 标题相似度阈值（字符二元组的Dice系数）
+const TITLE_SIMILARITY_THRESHOLD: f64 = 0.85;
+
+// ISBN-13 的加权和，权重依次为 1 和 3
+fn isbn13_sum(digits: &str) -> u32 {
+    digits
+        .chars()
+        .enumerate()
+        .filter_map(|(i, c)| Some(c.to_digit(10)? * if i % 2 == 0 { 1 } else { 3 }))
+        .sum()
+}
+
+// 规范化ISBN：去掉连字符，ISBN-10 转换为 ISBN-13，便于不同写法互相匹配
+// 校验位不正确的值（通常是录入错误或其他编号）不作为ISBN
+fn normalize_isbn(value: &str) -> Option<String> {
+    let digits: String = value
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    match digits.len() {
+        13 if digits.chars().all(|c| c.is_ascii_digit()) => {
+            isbn13_sum(&digits).is_multiple_of(10).then_some(digits)
+        }
+        10 if digits[..9].chars().all(|c| c.is_ascii_digit()) => {
+            // ISBN-10 的权重从 10 递减到 1，最后一位可以是 X（表示10）
+            let sum: u32 = digits
+                .chars()
+                .zip((1..=10).rev())
+                .map(|(c, weight)| match c {
+                    'X' => Some(10 * weight),
+                    c => c.to_digit(10).map(|d| d * weight),
+                })
+                .sum::<Option<u32>>()?;
+            if !sum.is_multiple_of(11) {
+                return None;
+            }
+            let body = format!("978{}", &digits[..9]);
+            Some(format!("{}{}", body, (10 - isbn13_sum(&body) % 10) % 10))
+        }
+        _ => None,
+    }
+}
+
+// 规范化标识符，只使用ISBN和UUID，其他标识符（如书店内部编号）不可靠
+fn normalized_identifiers(record: &LibraryRecord) -> Vec<String> {
+    record
+        .metadata
+        .identifiers
+        .iter()
+        .filter_map(|identifier| match identifier.scheme.as_deref() {
+            Some("isbn") => normalize_isbn(&identifier.value).map(|isbn| format!("isbn:{}", isbn)),
+            Some("uuid") => {
+                let uuid: String = identifier
+                    .value
+                    .to_lowercase()
+                    .trim_start_matches("urn:uuid:")
+                    .chars()
+                    .filter(|c| c.is_ascii_hexdigit())
+                    .collect();
+                (uuid.len() == 32).then(|| format!("uuid:{}", uuid))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+// 规范化文本：转小写，去掉括号中的内容（版本说明等）、空白和标点
+fn normalize_text(text: &str) -> String {
+    let mut depth = 0;
+    let mut normalized = String::new();
+    for c in text.to_lowercase().chars() {
+        match c {
+            '(' | '[' | '（' | '【' | '〔' => depth += 1,
+            ')' | ']' | '）' | '】' | '〕' => depth = (depth - 1).max(0),
+            c if depth == 0 && c.is_alphanumeric() => normalized.push(c),
+            _ => {}
+        }
+    }
+    normalized
+}
+
+// 字符二元组
+type Bigram = (char, char);
+
+// 标题的字符二元组集合，单个字符的标题使用字符本身
+fn bigrams(text: &str) -> HashSet<Bigram> {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() == 1 {
+        return HashSet::from([(chars[0], chars[0])]);
+    }
+    chars.windows(2).map(|w| (w[0], w[1])).collect()
+}
+
+// 两个二元组集合的Dice系数
+fn similarity(a: &HashSet<Bigram>, b: &HashSet<Bigram>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    2.0 * a.intersection(b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+// 并查集，用于把两两匹配的书籍合并为一组
+fn find_root(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[i] = root;
+    root
+}
+
+// 查找重复书籍：标识符相同，或标题相似且作者相同（缺少作者时只比较标题）
+pub async fn find_duplicate_books(app_handle: &AppHandle) -> Result<Vec<DuplicateGroup>, String> {
+    let index = reconcile_library_index(app_handle).await?;
+    let books_dir = get_books_dir(app_handle)?;
+    let records = index.books;
+
+    let mut parents: Vec<usize> = (0..records.len()).collect();
+    let mut edges: Vec<(usize, usize, DuplicateReason)> = Vec::new();
+
+    // 标识符相同
+    let mut by_identifier: HashMap<String, usize> = HashMap::new();
+    for (i, record) in records.iter().enumerate() {
+        for identifier in normalized_identifiers(record) {
+            match by_identifier.get(&identifier) {
+                Some(&j) => edges.push((j, i, DuplicateReason::Identifier)),
+                None => {
+                    by_identifier.insert(identifier, i);
+                }
+            }
+        }
+    }
+
+    // 标题相似且作者相同，缺少作者时无法区分同名的不同书籍，不作比较
+    let keys: Vec<(HashSet<Bigram>, Option<String>)> = records
+        .iter()
+        .map(|record| {
+            let title = record.metadata.title.as_deref().unwrap_or_else(|| {
+                Path::new(&record.file_name)
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .unwrap_or_default()
+            });
+            let author = record
+                .metadata
+                .creators
+                .first()
+                .map(|c| normalize_text(&c.name))
+                .filter(|name| !name.is_empty());
+            (bigrams(&normalize_text(title)), author)
+        })
+        .collect();
+    for i in 0..records.len() {
+        for j in (i + 1)..records.len() {
+            let authors_match = match (&keys[i].1, &keys[j].1) {
+                (Some(a), Some(b)) => a == b,
+                _ => false,
+            };
+            if authors_match && similarity(&keys[i].0, &keys[j].0) >= TITLE_SIMILARITY_THRESHOLD {
+                edges.push((i, j, DuplicateReason::TitleAuthor));
+            }
+        }
+    }
+
+    for &(i, j, _) in &edges {
+        let (root_i, root_j) = (find_root(&mut parents, i), find_root(&mut parents, j));
+        parents[root_j] = root_i;
+    }
+
+    let mut groups: HashMap<usize, DuplicateGroup> = HashMap::new();
+    for (i, record) in records.iter().enumerate() {
+        let root = find_root(&mut parents, i);
+        groups
+            .entry(root)
+            .or_insert_with(|| DuplicateGroup {
+                reasons: Vec::new(),
+                books: Vec::new(),
+            })
+            .books
+            .push(epub_file_from_record(&books_dir, record));
+    }
+    for (i, _, reason) in edges {
+        let root = find_root(&mut parents, i);
+        if let Some(group) = groups.get_mut(&root) {
+            if !group.reasons.contains(&reason) {
+                group.reasons.push(reason);
+            }
+        }
+    }
+
+    let mut groups: Vec<DuplicateGroup> = groups
+        .into_values()
+        .filter(|group| group.books.len() > 1)
+        .collect();
+    for group in groups.iter_mut() {
+        // 最早导入的排在前面，前端默认保留第一本
+        group.books.sort_by_key(|book| book.added_at);
+    }
+    groups.sort_by_key(|group| group.books[0].added_at);
+    Ok(groups)
+}
+
+// 读取书籍目录中的书签
+fn read_bookmark(book_dir: &Path) -> Option<BookMark> {
+    fs::read_to_string(book_dir.join("mark.json"))
+        .ok()
+        .and_then(|json_data| serde_json::from_str(&json_data).ok())
+}
+
+// 将其他书籍目录中的书签、阅读进度和记录、最后打开时间合并到 keep_dir
+// 同一位置已有书签时保留 keep_dir 的书签；keep_dir 没有阅读进度时，使用其他目录中最近的进度
+pub fn merge_book_state(
+    keep_dir: &Path,
+    keep_path: &Path,
+    other_dirs: &[PathBuf],
+) -> Result<(), String> {
+    let mut bookmark = read_bookmark(keep_dir)
+        .unwrap_or_else(|| BookMark::new(keep_path.to_string_lossy().to_string()));
+    let mut last_opened = get_last_opened(keep_dir);
+    let keep_progress = read_progress(keep_dir);
+    let mut progress: Option<ReadingProgress> = None;
+    let mut sessions = read_sessions(keep_dir);
+    let session_count = sessions.len();
+
+    for other_dir in other_dirs {
+        if let Some(other) = read_bookmark(other_dir) {
+            bookmark.merge_marks(other.list);
+        }
+        last_opened = last_opened.max(get_last_opened(other_dir));
+        sessions.extend(read_sessions(other_dir));
+        if let Some(other) = read_progress(other_dir) {
+            if progress
+                .as_ref()
+                .is_none_or(|p| p.updated_at < other.updated_at)
+            {
+                progress = Some(other);
+            }
+        }
+    }
+
+    bookmark.book_path = keep_path.to_string_lossy().to_string();
+    let json_data = serde_json::to_string(&bookmark)
+        .map_err(|e| format!("Failed to serialize bookmark: {}", e))?;
+    fs::write(keep_dir.join("mark.json"), json_data)
+        .map_err(|e| format!("Failed to write bookmark: {}", e))?;
+    if let Some(last_opened) = last_opened {
+        fs::write(keep_dir.join(".lastopened"), last_opened.to_string())
+            .map_err(|e| format!("Failed to write last opened time: {}", e))?;
+    }
+
+    if let (None, Some(progress)) = (keep_progress, progress) {
+        write_progress(keep_dir, &progress)?;
+    }
+    if sessions.len() != session_count {
+        sessions.sort_by_key(|s| s.start);
+        write_sessions(keep_dir, &sessions)?;
+    }
+    Ok(())
+}
+
+// 合并重复书籍：保留 keep_id，将其他书籍的书签、阅读进度和记录、最后打开时间和书架归属移过来，然后移到回收站
+// 不同版本的页码和CFI不一定对应，同一位置已有书签时保留 keep_id 的书签
+pub async fn merge_duplicate_books(
+    app_handle: &AppHandle,
+    keep_id: &str,
+    remove_ids: Vec<String>,
+) -> Result<EpubFile, String> {
+    let keep_dir = get_book_dir(app_handle, keep_id)?;
+    let index = reconcile_library_index(app_handle).await?;
+    let keep_record = index
+        .books
+        .iter()
+        .find(|r| r.id == keep_id)
+        .ok_or_else(|| format!("Book not found: {}", keep_id))?;
+    let keep_path = keep_dir.join(&keep_record.file_name);
+
+    let mut remove_dirs = Vec::new();
+    for remove_id in remove_ids.iter().filter(|id| id.as_str() != keep_id) {
+        let remove_dir = get_book_dir(app_handle, remove_id)?;
+        if !remove_dir.exists() {
+            return Err(format!("Book not found: {}", remove_id));
+        }
+        remove_dirs.push(remove_dir);
+    }
+    merge_book_state(&keep_dir, &keep_path, &remove_dirs)?;
+
+    for remove_id in remove_ids.iter().filter(|id| id.as_str() != keep_id) {
+        replace_book_in_collections(app_handle, remove_id, keep_id)?;
+        delete_book(app_handle, remove_id).await?;
+    }
+
+    let books_dir = get_books_dir(app_handle)?;
+    Ok(epub_file_from_record(&books_dir, keep_record))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_isbn_converts_isbn10_to_isbn13() {
+        assert_eq!(
+            normalize_isbn("0-306-40615-2").as_deref(),
+            Some("9780306406157")
+        );
+        assert_eq!(
+            normalize_isbn("080442957x").as_deref(),
+            Some("9780804429573")
+        );
+    }
+
+    #[test]
+    fn normalize_isbn_strips_separators() {
+        assert_eq!(
+            normalize_isbn("978-0-306-40615-7").as_deref(),
+            Some("9780306406157")
+        );
+        assert_eq!(
+            normalize_isbn("978 0 306 40615 7").as_deref(),
+            normalize_isbn("0306406152").as_deref()
+        );
+    }
+
+    #[test]
+    fn normalize_isbn_rejects_other_values() {
+        assert_eq!(normalize_isbn(""), None);
+        assert_eq!(normalize_isbn("12345"), None);
+        assert_eq!(normalize_isbn("97803064061X7"), None);
+        assert_eq!(normalize_isbn("X306406152"), None);
+    }
+
+    #[test]
+    fn normalize_isbn_rejects_bad_check_digits() {
+        assert_eq!(normalize_isbn("978-0-306-40615-8"), None);
+        assert_eq!(normalize_isbn("0-306-40615-3"), None);
+        assert_eq!(normalize_isbn("030640615X"), None);
+        assert_eq!(
+            normalize_isbn("123456789X").as_deref(),
+            Some("9781234567897")
+        );
+    }
+}
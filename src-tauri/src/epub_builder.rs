@@ -0,0 +1,375 @@
+use crate::file::import_epub_file;
+use crate::model::{EpubFile, TocItem};
+use crate::util::is_cjk;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use tauri::AppHandle;
+use tauri::Manager;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, DateTime, ZipWriter};
+
+// 生成的章节正文使用的样式
+const GENERATED_STYLE: &str = "body { margin: 0 5%; line-height: 1.6; }
+h1, h2, h3, h4, h5, h6 { line-height: 1.3; }
+pre { white-space: pre-wrap; word-wrap: break-word; }
+code, pre { font-family: monospace; }
+img { max-width: 100%; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #999; padding: 0.2em 0.5em; }
+blockquote { margin-left: 1em; padding-left: 1em; border-left: 3px solid #ccc; }
+p.text { text-indent: 2em; margin: 0.5em 0; }
+";
+
+// 由其他格式转换而来的书籍
+pub struct GeneratedBook {
+    pub title: String,
+    pub author: Option<String>,
+    pub language: String,
+    pub chapters: Vec<GeneratedChapter>,
+    // 扁平的目录，按 level 组织层级，href 为章节文件名（可带 #锚点）
+    pub toc: Vec<TocItem>,
+    pub resources: Vec<GeneratedResource>,
+}
+
+// 一个章节，body 为 XHTML 的 body 内容
+pub struct GeneratedChapter {
+    pub title: String,
+    pub body: String,
+}
+
+// 章节引用的图片等资源，name 为相对于 OEBPS 的路径
+pub struct GeneratedResource {
+    pub name: String,
+    pub mime: String,
+    pub data: Vec<u8>,
+}
+
+// 第 index 个章节（从0开始）的文件名
+pub fn chapter_file_name(index: usize) -> String {
+    format!("chapter_{:04}.xhtml", index + 1)
+}
+
+// 转义 XML 文本和属性值
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // XML 1.0 不允许的控制字符直接丢弃
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// 根据正文粗略判断语言，中日文字符占多数时视为中文
+pub fn guess_language(text: &str) -> &'static str {
+    let (mut cjk, mut letters) = (0usize, 0usize);
+    for c in text.chars().filter(|c| c.is_alphabetic()).take(10_000) {
+        letters += 1;
+        if is_cjk(c) {
+            cjk += 1;
+        }
+    }
+    if letters > 0 && cjk * 2 >= letters {
+        "zh"
+    } else {
+        "en"
+    }
+}
+
+// 将扁平的目录按 level 组织为嵌套结构，跳级的条目挂在最近的上级条目下
+fn nest_toc(flat: &[TocItem]) -> Vec<TocItem> {
+    let mut roots: Vec<TocItem> = Vec::new();
+    let mut open: Vec<TocItem> = Vec::new();
+    for item in flat {
+        while open.last().is_some_and(|last| last.level >= item.level) {
+            let done = open.pop().unwrap();
+            match open.last_mut() {
+                Some(parent) => parent.subitems.push(done),
+                None => roots.push(done),
+            }
+        }
+        open.push(TocItem {
+            subitems: Vec::new(),
+            ..item.clone()
+        });
+    }
+    while let Some(done) = open.pop() {
+        match open.last_mut() {
+            Some(parent) => parent.subitems.push(done),
+            None => roots.push(done),
+        }
+    }
+    roots
+}
+
+// 由书籍内容生成固定的 UUID，同样的内容每次生成的 epub 完全相同，导入时可以去重
+fn content_uuid(book: &GeneratedBook) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(book.title.as_bytes());
+    for chapter in &book.chapters {
+        hasher.update(chapter.title.as_bytes());
+        hasher.update(chapter.body.as_bytes());
+    }
+    for resource in &book.resources {
+        hasher.update(&resource.data);
+    }
+    let hex = format!("{:x}", hasher.finalize());
+    format!(
+        "{}-{}-5{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[13..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+fn chapter_xhtml(book: &GeneratedBook, chapter: &GeneratedChapter) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" xml:lang=\"{lang}\" lang=\"{lang}\">\n<head>\n<meta charset=\"UTF-8\"/>\n<title>{title}</title>\n<link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>\n</head>\n<body>\n{body}\n</body>\n</html>\n",
+        lang = escape_xml(&book.language),
+        title = escape_xml(&chapter.title),
+        body = chapter.body
+    )
+}
+
+fn nav_list(items: &[TocItem], out: &mut String) {
+    out.push_str("<ol>\n");
+    for item in items {
+        out.push_str(&format!(
+            "<li><a href=\"{}\">{}</a>",
+            escape_xml(&item.href),
+            escape_xml(&item.label)
+        ));
+        if !item.subitems.is_empty() {
+            out.push('\n');
+            nav_list(&item.subitems, out);
+        }
+        out.push_str("</li>\n");
+    }
+    out.push_str("</ol>\n");
+}
+
+fn nav_xhtml(book: &GeneratedBook, toc: &[TocItem]) -> String {
+    let mut list = String::new();
+    nav_list(toc, &mut list);
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" xml:lang=\"{lang}\" lang=\"{lang}\">\n<head>\n<meta charset=\"UTF-8\"/>\n<title>{title}</title>\n</head>\n<body>\n<nav epub:type=\"toc\" id=\"toc\">\n<h1>{title}</h1>\n{list}</nav>\n</body>\n</html>\n",
+        lang = escape_xml(&book.language),
+        title = escape_xml(&book.title),
+    )
+}
+
+fn ncx_points(items: &[TocItem], play_order: &mut usize, out: &mut String) {
+    for item in items {
+        *play_order += 1;
+        out.push_str(&format!(
+            "<navPoint id=\"navPoint-{order}\" playOrder=\"{order}\">\n<navLabel><text>{}</text></navLabel>\n<content src=\"{}\"/>\n",
+            escape_xml(&item.label),
+            escape_xml(&item.href),
+            order = play_order
+        ));
+        ncx_points(&item.subitems, play_order, out);
+        out.push_str("</navPoint>\n");
+    }
+}
+
+// EPUB 2 的 NCX 目录，兼容只读取 NCX 的阅读器
+fn toc_ncx(book: &GeneratedBook, uuid: &str, toc: &[TocItem]) -> String {
+    let mut points = String::new();
+    ncx_points(toc, &mut 0, &mut points);
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ncx xmlns=\"http://www.daisy.org/z3986/2005/ncx/\" version=\"2005-1\">\n<head>\n<meta name=\"dtb:uid\" content=\"urn:uuid:{uuid}\"/>\n</head>\n<docTitle><text>{title}</text></docTitle>\n<navMap>\n{points}</navMap>\n</ncx>\n",
+        title = escape_xml(&book.title),
+    )
+}
+
+fn content_opf(book: &GeneratedBook, uuid: &str) -> String {
+    let creator = book
+        .author
+        .as_ref()
+        .map(|author| format!("<dc:creator>{}</dc:creator>\n", escape_xml(author)))
+        .unwrap_or_default();
+    let mut manifest = String::from(
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n<item id=\"ncx\" href=\"toc.ncx\" media-type=\"application/x-dtbncx+xml\"/>\n<item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n",
+    );
+    let mut spine = String::new();
+    for index in 0..book.chapters.len() {
+        manifest.push_str(&format!(
+            "<item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n",
+            index + 1,
+            chapter_file_name(index)
+        ));
+        spine.push_str(&format!("<itemref idref=\"chapter-{}\"/>\n", index + 1));
+    }
+    for (index, resource) in book.resources.iter().enumerate() {
+        manifest.push_str(&format!(
+            "<item id=\"resource-{}\" href=\"{}\" media-type=\"{}\"/>\n",
+            index + 1,
+            escape_xml(&resource.name),
+            escape_xml(&resource.mime)
+        ));
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\">\n<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n<dc:identifier id=\"book-id\">urn:uuid:{uuid}</dc:identifier>\n<dc:title>{title}</dc:title>\n{creator}<dc:language>{lang}</dc:language>\n<meta property=\"dcterms:modified\">2000-01-01T00:00:00Z</meta>\n</metadata>\n<manifest>\n{manifest}</manifest>\n<spine toc=\"ncx\">\n{spine}</spine>\n</package>\n",
+        title = escape_xml(&book.title),
+        lang = escape_xml(&book.language),
+    )
+}
+
+// 将转换后的书籍写为 epub 文件
+// 压缩包内的时间固定，保证同样的内容得到同样的哈希值
+pub fn write_generated_epub(book: &GeneratedBook, dest: &Path) -> Result<(), String> {
+    let uuid = content_uuid(book);
+    let toc = nest_toc(&book.toc);
+
+    let mut files: Vec<(String, Vec<u8>)> = vec![
+        (
+            "META-INF/container.xml".to_string(),
+            b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n<rootfiles>\n<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>\n</rootfiles>\n</container>\n".to_vec(),
+        ),
+        ("OEBPS/content.opf".to_string(), content_opf(book, &uuid).into_bytes()),
+        ("OEBPS/nav.xhtml".to_string(), nav_xhtml(book, &toc).into_bytes()),
+        ("OEBPS/toc.ncx".to_string(), toc_ncx(book, &uuid, &toc).into_bytes()),
+        ("OEBPS/style.css".to_string(), GENERATED_STYLE.as_bytes().to_vec()),
+    ];
+    for (index, chapter) in book.chapters.iter().enumerate() {
+        files.push((
+            format!("OEBPS/{}", chapter_file_name(index)),
+            chapter_xhtml(book, chapter).into_bytes(),
+        ));
+    }
+
+    let file = File::create(dest).map_err(|e| format!("Failed to create epub file: {}", e))?;
+    let mut zip = ZipWriter::new(file);
+    let stored = FileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .last_modified_time(DateTime::default());
+    let deflated = stored.compression_method(CompressionMethod::Deflated);
+
+    // mimetype 必须是第一个文件且不压缩
+    zip.start_file("mimetype", stored)
+        .and_then(|_| Ok(zip.write_all(b"application/epub+zip")?))
+        .map_err(|e| format!("Failed to write epub file: {}", e))?;
+    let resources = book
+        .resources
+        .iter()
+        .map(|resource| (format!("OEBPS/{}", resource.name), &resource.data));
+    for (name, data) in files
+        .iter()
+        .map(|(name, data)| (name.clone(), data))
+        .chain(resources)
+    {
+        zip.start_file(name.as_str(), deflated)
+            .and_then(|_| Ok(zip.write_all(data)?))
+            .map_err(|e| format!("Failed to write {} to epub: {}", name, e))?;
+    }
+    zip.finish()
+        .map_err(|e| format!("Failed to finish epub file: {}", e))?;
+    Ok(())
+}
+
+// 将转换后的书籍生成 epub 并按普通 epub 导入书库
+// file_stem 为导入后的文件名（不含扩展名）
+pub async fn import_generated_book(
+    app_handle: &AppHandle,
+    book: GeneratedBook,
+    file_stem: &str,
+) -> Result<(EpubFile, bool), String> {
+    let temp_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?
+        .join("convert_tmp")
+        .join(format!("{:016x}", rand::thread_rng().gen::<u64>()));
+    fs::create_dir_all(&temp_dir)
+        .map_err(|e| format!("Failed to create temporary directory: {}", e))?;
+
+    // 文件名中不能包含路径分隔符等字符
+    let file_stem: String = file_stem
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect();
+    let file_stem = match file_stem.trim() {
+        "" => "book",
+        stem => stem,
+    };
+    let epub_path = temp_dir.join(format!("{}.epub", file_stem));
+    let path = epub_path.clone();
+    let result = match tokio::task::spawn_blocking(move || write_generated_epub(&book, &path))
+        .await
+        .map_err(|e| format!("Convert task failed: {}", e))
+    {
+        Ok(Ok(())) => import_epub_file(app_handle, &epub_path).await,
+        Ok(Err(e)) | Err(e) => Err(e),
+    };
+    let _ = fs::remove_dir_all(&temp_dir);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(label: &str, level: usize) -> TocItem {
+        TocItem {
+            label: label.to_string(),
+            href: format!("{}.xhtml", label),
+            level,
+            spine_index: None,
+            subitems: Vec::new(),
+        }
+    }
+
+    fn labels(items: &[TocItem]) -> Vec<String> {
+        items
+            .iter()
+            .map(|item| match labels(&item.subitems).join(",") {
+                children if children.is_empty() => item.label.clone(),
+                children => format!("{}[{}]", item.label, children),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nest_toc_builds_hierarchy_from_levels() {
+        let flat = [
+            item("a", 0),
+            item("a1", 1),
+            item("a1x", 2),
+            item("a2", 1),
+            item("b", 0),
+        ];
+        assert_eq!(labels(&nest_toc(&flat)), ["a[a1[a1x],a2]", "b"]);
+    }
+
+    #[test]
+    fn nest_toc_attaches_skipped_levels_to_nearest_parent() {
+        let flat = [item("a", 0), item("a1", 2), item("a2", 1), item("b", 1)];
+        assert_eq!(labels(&nest_toc(&flat)), ["a[a1,a2,b]"]);
+        // 第一个条目不是顶层时也作为顶层条目
+        let flat = [item("x", 2), item("y", 0)];
+        assert_eq!(labels(&nest_toc(&flat)), ["x", "y"]);
+    }
+
+    #[test]
+    fn escape_xml_escapes_markup_and_drops_control_characters() {
+        assert_eq!(
+            escape_xml("<a href=\"x\">Tom & 'Jerry'</a>\u{1}\n"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; &apos;Jerry&apos;&lt;/a&gt;\n"
+        );
+    }
+}
@@ -1,46 +1,104 @@
-use crate::cover::use_default_cover;
-use crate::model::EpubFile;
+use crate::cover::save_cover;
+use crate::duplicate::merge_book_state;
+use crate::fulltext::{index_book_in_background, remove_book_from_fulltext_index};
+use crate::jobs::spawn_job;
+use crate::library::{
+    build_library_record, epub_file_from_record, get_books_dir, reconcile_library_index,
+    upsert_library_record,
+};
+use crate::markdown::{import_markdown, is_markdown_dir, is_markdown_file};
+use crate::metadata::{read_epub_metadata, save_metadata};
+use crate::model::{EpubFile, JobKind, LibraryChangedEvent};
+use crate::placeholder::render_placeholder_cover;
+use crate::trash::is_book_trashed;
+use crate::txt::{import_txt, is_text_file};
+use crate::watch::LIBRARY_CHANGED_EVENT;
 use epub::doc::EpubDoc;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
-use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
-use tauri::AppHandle;
-use tauri::Manager;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::OwnedMutexGuard;
 
-// 保存封面到本地
-// 返回封面保存的路径
-async fn read_epub_cover(dir: &str, epub_path: &str) -> Result<String, String> {
+// 正在导入的书籍，同样内容的文件同时导入时按书籍ID排队
+type ImportLocks = HashMap<String, Arc<tokio::sync::Mutex<()>>>;
+static IMPORT_LOCKS: LazyLock<Mutex<ImportLocks>> = LazyLock::new(Default::default);
+
+// 导入一本书期间持有的锁，释放时没有其他等待者则从表中移除
+pub(crate) struct ImportGuard {
+    id: String,
+    _guard: OwnedMutexGuard<()>,
+}
+
+impl Drop for ImportGuard {
+    fn drop(&mut self) {
+        let mut locks = IMPORT_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+        // 表中和本守卫各持有一份引用
+        if locks
+            .get(&self.id)
+            .is_some_and(|lock| Arc::strong_count(lock) <= 2)
+        {
+            locks.remove(&self.id);
+        }
+    }
+}
+
+// 书籍是否正在导入（复制文件、提取封面等）
+pub fn is_book_importing(id: &str) -> bool {
+    IMPORT_LOCKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .contains_key(id)
+}
+
+pub(crate) async fn lock_book_import(id: &str) -> ImportGuard {
+    let lock = IMPORT_LOCKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(id.to_string())
+        .or_default()
+        .clone();
+    ImportGuard {
+        id: id.to_string(),
+        _guard: lock.lock_owned().await,
+    }
+}
+
+// 按封面的真实格式保存到本地，并生成缩略图
+// 返回封面文件名
+pub fn read_epub_cover(dir: &Path, epub_path: &Path) -> Result<String, String> {
     let mut doc = EpubDoc::new(epub_path).map_err(|e| e.to_string())?;
     let cover_data = match doc.get_cover() {
-        Some(data) => {
-            // 检查封面图片大小，如果小于1KB则认为已损坏，使用默认封面
-            if data.0.len() < 1024 {
-                (
-                    use_default_cover()
-                        .map_err(|e| format!("Failed to read default cover file: {}", e))?,
-                    "image/png".to_string(),
-                )
-            } else {
-                data
-            }
+        // 检查封面图片大小，如果小于1KB则认为已损坏，生成封面
+        Some(data) if data.0.len() >= 1024 => data,
+        _ => {
+            let id = dir
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let title = doc.mdata("title").unwrap_or_else(|| {
+                epub_path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            });
+            let author = doc.mdata("creator");
+            (
+                render_placeholder_cover(&id, &title, author.as_deref())?,
+                "image/png".to_string(),
+            )
         }
-        None => (
-            use_default_cover().map_err(|e| format!("Failed to read default cover file: {}", e))?,
-            "image/png".to_string(),
-        ),
     };
-    let (image_data, _mime_type) = cover_data;
-    let f = File::create(format!("{}/cover.jpg", dir));
-    assert!(f.is_ok());
-    let mut f = f.unwrap();
-    f.write_all(&image_data).map_err(|e| e.to_string())?;
-    Ok(format!("{}/cover.jpg", dir))
+    let (image_data, mime_type) = cover_data;
+    save_cover(dir, &image_data, &mime_type)
 }
 
 // 获取系统当前时间的Unix时间戳
-fn get_current_timestamp() -> Result<u64, String> {
+pub fn get_current_timestamp() -> Result<u64, String> {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .map_err(|e| e.to_string())
@@ -62,157 +120,299 @@ pub async fn update_last_opened(file_path: &str) -> Result<(), String> {
 }
 
 // 获取最后打开时间
-fn get_last_opened(dir_path: &Path) -> Option<u64> {
+pub fn get_last_opened(dir_path: &Path) -> Option<u64> {
     std::fs::read_to_string(dir_path.join(".lastopened"))
         .ok()
         .and_then(|s| s.parse().ok())
 }
 
+// 查找书籍目录中的epub文件
+pub fn find_epub_in_dir(hash_dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(hash_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| path.extension().map(|ext| ext == "epub").unwrap_or(false))
+}
+
 // 加载本地所有的epub文件
+// 从书库索引中读取，不再每次打开epub文件
 pub async fn load_all_local_epub_files(app_handle: &AppHandle) -> Result<Vec<EpubFile>, String> {
-    // 加载/com.rbook.app/books/xxxxxxx/xxxx.epub 和 /com.rbook.app/books/xxxxxx/cover.jpg
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-    let books_dir = app_dir.join("books");
-    if !books_dir.exists() {
-        return Ok(vec![]);
-    }
-    let mut epub_files = Vec::new();
-    // /com.rbook.app/books/xxxxxxxx/xxxx.epub
-    // 其中xxxxxx为md5的值
-    for hash_dir_entry in std::fs::read_dir(books_dir)
-        .map_err(|e| format!("Failed to read books directory: {}", e))?
-    {
-        let hash_dir_entry =
-            hash_dir_entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
-        let hash_dir_path = hash_dir_entry.path();
-
-        // 检查它是否是一个目录
-        if hash_dir_path.is_dir() {
-            // 遍历MD5哈希目录中的文件
-            for file_entry in std::fs::read_dir(&hash_dir_path)
-                .map_err(|e| format!("Failed to read hash directory: {}", e))?
-            {
-                let file_entry =
-                    file_entry.map_err(|e| format!("Failed to read file entry: {}", e))?;
-                let file_path = file_entry.path();
-
-                // 如果是epub文件
-                if file_path
-                    .extension()
-                    .map(|ext| ext == "epub")
-                    .unwrap_or(false)
-                {
-                    let cover_path = hash_dir_path.join("cover.jpg");
-                    // 检查封面文件是否存在
-                    if !cover_path.exists() {
-                        // 如果不存在，读取epub文件的封面
-                        read_epub_cover(
-                            hash_dir_path.to_str().unwrap(),
-                            file_path.to_str().unwrap(),
-                        )
-                        .await?;
-                    }
-                    let last_opened = get_last_opened(&hash_dir_path);
-
-                    epub_files.push(EpubFile {
-                        cover: cover_path.to_str().unwrap().to_string(),
-                        path: file_path.to_str().unwrap().to_string(),
-                        last_opened,
-                    });
-                }
-            }
-        }
-    }
-    // println!("epub_files: {:?}", epub_files);
-
-    Ok(epub_files)
+    let books_dir = get_books_dir(app_handle)?;
+    let index = reconcile_library_index(app_handle).await?;
+    Ok(index
+        .books
+        .iter()
+        .map(|record| epub_file_from_record(&books_dir, record))
+        .collect())
 }
 
 // 将传来的路径的文件复制到本地
+// Markdown 文件或文件夹、TXT 文件会先转换为 epub，其他文件夹请使用 import_epub_directory
 // 返回保存的本地路径
 pub async fn save_file_and_return_local_path(
     app_handle: &AppHandle,
     origin_path: &str,
 ) -> Result<EpubFile, String> {
-    let origin_path = Path::new(origin_path);
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-    let books_dir = app_dir.join("books");
+    import_file(app_handle, Path::new(origin_path), false)
+        .await?
+        .map(|(epub_file, _)| epub_file)
+        .ok_or_else(|| format!("Book is in the trash: {}", origin_path))
+}
+
+// 导入一个文件或 Markdown 文件夹，返回书籍以及是否为新导入
+// skip_trashed 为 true 时（监视文件夹自动导入）回收站中已有的书籍说明用户删除过，不再导入，返回空
+pub async fn import_file(
+    app_handle: &AppHandle,
+    path: &Path,
+    skip_trashed: bool,
+) -> Result<Option<(EpubFile, bool)>, String> {
+    if path.is_dir() {
+        let dir = path.to_path_buf();
+        let is_markdown = tokio::task::spawn_blocking(move || is_markdown_dir(&dir))
+            .await
+            .map_err(|e| format!("Scan task failed: {}", e))?;
+        if !is_markdown {
+            return Err(format!("Not a Markdown folder: {}", path.display()));
+        }
+        import_markdown(app_handle, path).await.map(Some)
+    } else if is_markdown_file(path) {
+        import_markdown(app_handle, path).await.map(Some)
+    } else if is_text_file(path) {
+        import_txt(app_handle, path).await.map(Some)
+    } else {
+        // 计算文件的 SHA-256 哈希值作为书籍ID
+        let book_id = calculate_content_hash(path).await?;
+        if skip_trashed && is_book_trashed(app_handle, &book_id)? {
+            return Ok(None);
+        }
+        import_epub_with_id(app_handle, path, &book_id)
+            .await
+            .map(Some)
+    }
+}
+
+// 导入单个epub文件
+// 返回epub文件信息以及是否为新导入（false表示书库中已存在）
+pub async fn import_epub_file(
+    app_handle: &AppHandle,
+    origin_path: &Path,
+) -> Result<(EpubFile, bool), String> {
+    // 计算文件的 SHA-256 哈希值作为书籍ID
+    let book_id = calculate_content_hash(origin_path).await?;
+    import_epub_with_id(app_handle, origin_path, &book_id).await
+}
+
+// 按已经计算出的书籍ID导入epub文件，避免重复计算哈希值
+async fn import_epub_with_id(
+    app_handle: &AppHandle,
+    origin_path: &Path,
+    book_id: &str,
+) -> Result<(EpubFile, bool), String> {
+    let books_dir = get_books_dir(app_handle)?;
     if !books_dir.exists() {
         std::fs::create_dir_all(&books_dir)
             .map_err(|e| format!("Failed to create books directory: {}", e))?;
     }
     // /com.rbook.app/books/xxxxxxxx/xxxx.epub
-    // 其中xxxxxx为md5的值
-
-    // 计算文件的 MD5 哈希值
-    let md5_hash = calculate_md5_hash(origin_path.to_str().unwrap()).await?;
+    // 其中xxxxxx为sha256的值
+    let _import_guard = lock_book_import(book_id).await;
 
     // 创建hash值的文件夹 并将文件复制过去
-    let hash_dir = books_dir.join(&md5_hash);
-    if !hash_dir.exists() {
+    let hash_dir = books_dir.join(book_id);
+    let created_dir = !hash_dir.exists();
+    if created_dir {
         std::fs::create_dir_all(&hash_dir)
             .map_err(|e| format!("Failed to create hash directory: {}", e))?;
     }
 
+    // 同样内容的书已在书库中（文件名可能不同），直接返回已有的书籍
+    if let Some(existing_path) = find_epub_in_dir(&hash_dir) {
+        let record = build_library_record(&hash_dir, &existing_path)?;
+        upsert_library_record(app_handle, record.clone())?;
+        return Ok((epub_file_from_record(&books_dir, &record), false));
+    }
+
     let file_name = origin_path.file_name().ok_or("Failed to get file name")?;
     let dest_path = hash_dir.join(file_name);
 
-    // 如果文件已存在，直接返回路径
-    if dest_path.exists() {
-        let last_opened = get_last_opened(&hash_dir);
-
-        return Ok(EpubFile {
-            cover: format!("{}/cover.jpg", hash_dir.to_str().unwrap()),
-            path: dest_path.to_str().unwrap().to_string(),
-            last_opened,
-        });
-    }
+    // 复制文件、读取封面和解析元数据都是阻塞操作，放到阻塞线程池中执行
+    let origin = origin_path.to_path_buf();
+    let (dir, dest) = (hash_dir.clone(), dest_path.clone());
+    let record = tokio::task::spawn_blocking(move || {
+        // 先复制为临时文件再改名，复制完成前目录中不会出现不完整的epub
+        let partial = dest.with_extension("epub.part");
+        let result = (|| {
+            std::fs::copy(&origin, &partial).map_err(|e| format!("Failed to copy file: {}", e))?;
+            std::fs::rename(&partial, &dest).map_err(|e| format!("Failed to copy file: {}", e))?;
+            // 读取封面
+            read_epub_cover(&dir, &origin)?;
+            // 解析元数据
+            let metadata = read_epub_metadata(&dest)?;
+            save_metadata(&dir, &metadata)?;
+            build_library_record(&dir, &dest)
+        })();
+        // 导入失败时清理复制了一半的文件，本次新建的目录整个删除
+        if result.is_err() {
+            if created_dir {
+                let _ = std::fs::remove_dir_all(&dir);
+            } else {
+                let _ = std::fs::remove_file(&partial);
+                let _ = std::fs::remove_file(&dest);
+            }
+        }
+        result
+    })
+    .await
+    .map_err(|e| format!("Import task failed: {}", e))??;
 
-    // 复制文件
-    std::fs::copy(origin_path, &dest_path).map_err(|e| format!("Failed to copy file: {}", e))?;
-    // 读取封面
-    read_epub_cover(hash_dir.to_str().unwrap(), origin_path.to_str().unwrap()).await?;
+    // 写入书库索引，并在后台建立全文索引
+    upsert_library_record(app_handle, record.clone())?;
+    index_book_in_background(app_handle, book_id);
 
     // 返回epub文件的路径
-    let last_opened = get_last_opened(&hash_dir);
-
-    Ok(EpubFile {
-        cover: format!("{}/cover.jpg", hash_dir.to_str().unwrap()),
-        path: dest_path.to_str().unwrap().to_string(),
-        last_opened,
-    })
+    Ok((epub_file_from_record(&books_dir, &record), true))
 }
 
-// 计算文件的 MD5 哈希值
-pub async fn calculate_md5_hash(file_path: &str) -> Result<String, String> {
-    let path = Path::new(file_path);
+// 哈希计算时每次读取的块大小
+const HASH_CHUNK_SIZE: usize = 64 * 1024;
 
+// 以固定大小的块流式计算文件的 SHA-256 哈希值，避免将整个文件读入内存
+pub fn hash_file_sha256(path: &Path) -> Result<String, String> {
     let mut file =
         File::open(path).map_err(|e| format!("Failed to open file for hashing: {}", e))?;
 
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)
-        .map_err(|e| format!("Failed to read file for hashing: {}", e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; HASH_CHUNK_SIZE];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read file for hashing: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    // 返回 SHA-256 哈希值的十六进制字符串
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// 计算文件的 SHA-256 哈希值，在阻塞线程池中执行
+pub async fn calculate_content_hash(file_path: &Path) -> Result<String, String> {
+    let path = file_path.to_path_buf();
+    tokio::task::spawn_blocking(move || hash_file_sha256(&path))
+        .await
+        .map_err(|e| format!("Hash task failed: {}", e))?
+}
+
+// 判断目录名是否为旧版的MD5书籍ID（32位十六进制）
+pub fn is_legacy_md5_id(name: &str) -> bool {
+    name.len() == 32 && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// 迁移单个旧版书籍目录，返回迁移后的书籍ID
+// 目录整体重命名，mark.json、封面和 .lastopened 随目录一起保留
+// 同一本书已按新ID导入过时，将书签、阅读进度和最后打开时间合并过去后删除旧目录
+fn migrate_legacy_book_dir(books_dir: &Path, old_dir: &Path) -> Result<Option<String>, String> {
+    let Some(old_epub_path) = find_epub_in_dir(old_dir) else {
+        return Ok(None);
+    };
+    let new_id = hash_file_sha256(&old_epub_path)?;
+    let new_dir = books_dir.join(&new_id);
+    if new_dir.exists() {
+        let new_epub_path = find_epub_in_dir(&new_dir)
+            .ok_or_else(|| format!("{} exists without an epub file", new_dir.display()))?;
+        merge_book_state(&new_dir, &new_epub_path, &[old_dir.to_path_buf()])?;
+        std::fs::remove_dir_all(old_dir)
+            .map_err(|e| format!("Failed to remove {}: {}", old_dir.display(), e))?;
+        return Ok(Some(new_id));
+    }
 
-    let digest = md5::compute(&buffer);
+    std::fs::rename(old_dir, &new_dir)
+        .map_err(|e| format!("Failed to rename {}: {}", old_dir.display(), e))?;
 
-    // 返回 MD5 哈希值的十六进制字符串
-    Ok(format!("{:x}", digest))
+    // 书签中记录的书籍路径包含旧的目录名，需要同步更新
+    let mark_file_path = new_dir.join("mark.json");
+    if let (Ok(json_data), Some(file_name)) = (
+        std::fs::read_to_string(&mark_file_path),
+        old_epub_path.file_name(),
+    ) {
+        if let Ok(mut mark) = serde_json::from_str::<serde_json::Value>(&json_data) {
+            mark["book_path"] =
+                serde_json::Value::String(new_dir.join(file_name).to_string_lossy().to_string());
+            if let Ok(json_data) = serde_json::to_string(&mark) {
+                std::fs::write(&mark_file_path, json_data)
+                    .map_err(|e| format!("Failed to update bookmark file: {}", e))?;
+            }
+        }
+    }
+    Ok(Some(new_id))
 }
 
-// 读取EPUB文件内容
-// 返回二进制数据
-pub async fn read_epub_file_content(file_path: &str) -> Result<Vec<u8>, String> {
-    let path = Path::new(file_path);
-    let mut file = File::open(path).map_err(|e| format!("Failed to open EPUB file: {}", e))?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)
-        .map_err(|e| format!("Failed to read EPUB file content: {}", e))?;
-    Ok(buffer)
+// 提交后台任务，将旧版以MD5命名的书籍目录迁移为以SHA-256命名
+// 迁移需要计算每本书的哈希值，放到后台执行，不阻塞窗口显示；没有旧目录时不提交任务
+pub fn start_migration_job(app_handle: &AppHandle) -> Result<Option<String>, String> {
+    let books_dir = get_books_dir(app_handle)?;
+    let old_dirs: Vec<PathBuf> = std::fs::read_dir(&books_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| is_legacy_md5_id(&entry.file_name().to_string_lossy()))
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .collect()
+        })
+        .unwrap_or_default();
+    if old_dirs.is_empty() {
+        return Ok(None);
+    }
+
+    let handle = app_handle.clone();
+    spawn_job(app_handle, JobKind::Migration, move |job| async move {
+        let dir = books_dir.clone();
+        let migrated = tokio::task::spawn_blocking(move || {
+            job.set_total(old_dirs.len());
+            let mut migrated = Vec::new();
+            for old_dir in old_dirs {
+                if job.is_cancelled() {
+                    break;
+                }
+                let old_id = old_dir
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                match migrate_legacy_book_dir(&dir, &old_dir) {
+                    Ok(Some(new_id)) => {
+                        println!("Migrated book directory {} -> {}", old_id, new_id);
+                        migrated.push((old_id.clone(), new_id));
+                    }
+                    Ok(None) => {}
+                    Err(e) => println!("Failed to migrate {}: {}", old_dir.display(), e),
+                }
+                job.advance(Some(old_id));
+            }
+            migrated
+        })
+        .await
+        .map_err(|e| format!("Migration task failed: {}", e))?;
+
+        // 刷新书库索引和全文索引，并通知前端重新加载
+        let index = reconcile_library_index(&handle).await?;
+        for (old_id, new_id) in &migrated {
+            if let Err(e) = remove_book_from_fulltext_index(&handle, old_id) {
+                println!("Failed to remove {} from full-text index: {}", old_id, e);
+            }
+            index_book_in_background(&handle, new_id);
+        }
+        let imported: Vec<EpubFile> = index
+            .books
+            .iter()
+            .filter(|record| migrated.iter().any(|(_, new_id)| *new_id == record.id))
+            .map(|record| epub_file_from_record(&books_dir, record))
+            .collect();
+        let _ = handle.emit(LIBRARY_CHANGED_EVENT, LibraryChangedEvent { imported });
+        Ok(serde_json::json!({ "migrated": migrated.len() }))
+    })
+    .map(Some)
 }
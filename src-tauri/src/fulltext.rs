@@ -0,0 +1,573 @@
+use crate::file::find_epub_in_dir;
+use crate::jobs::spawn_job;
+use crate::library::{get_books_dir, get_library_record};
+use crate::model::{
+    BookSearchQuery, FullTextHit, FullTextIndex, IndexedBook, JobKind, SearchMatch,
+};
+use crate::navigation::load_book_navigation;
+use crate::search::{
+    build_matcher, chapter_label, read_chapter_text, search_chapter, spine_element_step,
+    ChapterText, Matcher,
+};
+use crate::util::{is_cjk, strip_diacritic};
+use epub::doc::EpubDoc;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::AppHandle;
+use tauri::Manager;
+
+// 索引格式版本，分词方式变化时增加，旧索引会被丢弃
+const FULLTEXT_INDEX_VERSION: u32 = 1;
+
+// 默认返回的结果数量
+const DEFAULT_FULLTEXT_LIMIT: usize = 50;
+
+// 超过该长度的单词（通常是链接或编码数据）不加入索引
+const MAX_TERM_CHARS: usize = 40;
+
+// 索引有变化后延迟保存，连续导入多本书时只写一次磁盘
+const SAVE_DELAY: Duration = Duration::from_secs(3);
+
+// 全文索引的内存状态
+struct FullTextState {
+    index: Option<FullTextIndex>,        // 首次使用时从磁盘读取
+    rebuilding: Option<HashSet<String>>, // 重建期间增删过的书籍ID，重建完成时以当前索引为准
+    save_scheduled: bool,
+}
+
+static FULLTEXT_STATE: Mutex<FullTextState> = Mutex::new(FullTextState {
+    index: None,
+    rebuilding: None,
+    save_scheduled: false,
+});
+
+// 一个章节中各个词出现的次数
+type ChapterTerms = HashMap<String, Vec<(u32, u32)>>; // term -> [(spine index, count)]
+
+// 全文索引文件路径 /com.rbook.app/index/fulltext.json
+fn get_fulltext_index_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+    Ok(app_dir.join("index").join("fulltext.json"))
+}
+
+// 分词：字母和数字按单词切分，中日文没有空格，按相邻两字切分
+// 索引和查询使用同样的分词，都去掉变音符号并转为小写
+pub fn tokenize(chars: &[char]) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut cjk_run: Vec<char> = Vec::new();
+
+    let flush_word = |word: &mut String, tokens: &mut Vec<String>| {
+        if !word.is_empty() && word.chars().count() <= MAX_TERM_CHARS {
+            tokens.push(std::mem::take(word));
+        }
+        word.clear();
+    };
+    let flush_cjk = |run: &mut Vec<char>, tokens: &mut Vec<String>| {
+        if run.len() == 1 {
+            tokens.push(run[0].to_string());
+        }
+        tokens.extend(run.windows(2).map(|pair| pair.iter().collect::<String>()));
+        run.clear();
+    };
+
+    for &c in chars {
+        let Some(c) = strip_diacritic(c) else {
+            continue;
+        };
+        if !c.is_alphanumeric() {
+            flush_word(&mut word, &mut tokens);
+            flush_cjk(&mut cjk_run, &mut tokens);
+        } else if is_cjk(c) {
+            flush_word(&mut word, &mut tokens);
+            cjk_run.push(c);
+        } else {
+            flush_cjk(&mut cjk_run, &mut tokens);
+            word.extend(c.to_lowercase());
+        }
+    }
+    flush_word(&mut word, &mut tokens);
+    flush_cjk(&mut cjk_run, &mut tokens);
+    tokens
+}
+
+// 提取一本书各章节的词频
+fn extract_book_terms(epub_path: &Path) -> Result<(u32, ChapterTerms), String> {
+    let mut doc = EpubDoc::new(epub_path).map_err(|e| e.to_string())?;
+    let mut terms: ChapterTerms = HashMap::new();
+    let mut chapters = 0;
+    let spine_element = spine_element_step(&mut doc);
+    for spine_index in 0..doc.spine.len() {
+        let Some(chapter) = read_chapter_text(&mut doc, spine_element, spine_index) else {
+            continue;
+        };
+        chapters += 1;
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for token in tokenize(&chapter.text.chars) {
+            *counts.entry(token).or_default() += 1;
+        }
+        for (term, count) in counts {
+            terms
+                .entry(term)
+                .or_default()
+                .push((spine_index as u32, count));
+        }
+    }
+    Ok((chapters, terms))
+}
+
+// 从索引中移除一本书，返回是否有变化
+fn remove_from_index(index: &mut FullTextIndex, id: &str) -> bool {
+    let keys: Vec<u32> = index
+        .books
+        .iter()
+        .filter(|(_, book)| book.id == id)
+        .map(|(key, _)| *key)
+        .collect();
+    if keys.is_empty() {
+        return false;
+    }
+    for key in &keys {
+        index.books.remove(key);
+    }
+    index.terms.retain(|_, postings| {
+        postings.retain(|(key, _, _)| !keys.contains(key));
+        !postings.is_empty()
+    });
+    true
+}
+
+// 将一本书加入索引，已存在时先移除旧的记录
+fn insert_into_index(index: &mut FullTextIndex, id: &str, chapters: u32, terms: ChapterTerms) {
+    remove_from_index(index, id);
+    let key = index.next_key;
+    index.next_key += 1;
+    index.books.insert(
+        key,
+        IndexedBook {
+            id: id.to_string(),
+            chapters,
+        },
+    );
+    for (term, postings) in terms {
+        index.terms.entry(term).or_default().extend(
+            postings
+                .into_iter()
+                .map(|(spine_index, count)| (key, spine_index, count)),
+        );
+    }
+}
+
+// 将一本书在 from 中的记录复制到 to，from 中没有时从 to 中移除
+fn copy_book_entry(from: &FullTextIndex, to: &mut FullTextIndex, id: &str) {
+    let Some((&key, book)) = from.books.iter().find(|(_, book)| book.id == id) else {
+        remove_from_index(to, id);
+        return;
+    };
+    let mut terms: ChapterTerms = HashMap::new();
+    for (term, postings) in &from.terms {
+        for &(_, spine_index, count) in postings.iter().filter(|(k, _, _)| *k == key) {
+            terms
+                .entry(term.clone())
+                .or_default()
+                .push((spine_index, count));
+        }
+    }
+    insert_into_index(to, id, book.chapters, terms);
+}
+
+// 读取磁盘上的索引，不存在、损坏或版本不同时返回空索引
+fn read_fulltext_index(index_path: &Path) -> FullTextIndex {
+    fs::read_to_string(index_path)
+        .ok()
+        .and_then(|json_data| serde_json::from_str::<FullTextIndex>(&json_data).ok())
+        .filter(|index| index.version == FULLTEXT_INDEX_VERSION)
+        .unwrap_or_else(|| FullTextIndex {
+            version: FULLTEXT_INDEX_VERSION,
+            ..Default::default()
+        })
+}
+
+// 写入索引，先写临时文件再替换，避免写入中断时损坏索引
+fn write_fulltext_index(index_path: &Path, index: &FullTextIndex) -> Result<(), String> {
+    if let Some(parent) = index_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create index directory: {}", e))?;
+    }
+    let json_data = serde_json::to_string(index)
+        .map_err(|e| format!("Failed to serialize full-text index: {}", e))?;
+    let temp_path = index_path.with_extension("json.tmp");
+    fs::write(&temp_path, json_data)
+        .map_err(|e| format!("Failed to write full-text index: {}", e))?;
+    fs::rename(&temp_path, index_path)
+        .map_err(|e| format!("Failed to replace full-text index: {}", e))
+}
+
+// 延迟保存索引，等待期间的其他修改一起保存
+fn schedule_save(state: &mut FullTextState, index_path: &Path) {
+    if state.save_scheduled {
+        return;
+    }
+    state.save_scheduled = true;
+    let index_path = index_path.to_path_buf();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(SAVE_DELAY).await;
+        let mut state = FULLTEXT_STATE.lock().unwrap_or_else(|e| e.into_inner());
+        state.save_scheduled = false;
+        if let Some(index) = &state.index {
+            if let Err(e) = write_fulltext_index(&index_path, index) {
+                println!("{}", e);
+            }
+        }
+    });
+}
+
+// 在锁内访问索引，f 返回 true 时表示 book_id 对应的书籍有变化，稍后保存到磁盘
+fn update_fulltext_index(
+    index_path: &Path,
+    book_id: Option<&str>,
+    f: impl FnOnce(&mut FullTextIndex) -> bool,
+) {
+    let mut state = FULLTEXT_STATE.lock().unwrap_or_else(|e| e.into_inner());
+    let index = state
+        .index
+        .get_or_insert_with(|| read_fulltext_index(index_path));
+    if !f(index) {
+        return;
+    }
+    if let (Some(id), Some(changed)) = (book_id, state.rebuilding.as_mut()) {
+        changed.insert(id.to_string());
+    }
+    schedule_save(&mut state, index_path);
+}
+
+// 在后台为一本书建立索引（导入或从回收站恢复时调用），失败时只打印错误
+pub fn index_book_in_background(app_handle: &AppHandle, id: &str) {
+    let paths = get_fulltext_index_path(app_handle)
+        .and_then(|index_path| Ok((index_path, get_books_dir(app_handle)?.join(id))));
+    let (index_path, book_dir) = match paths {
+        Ok(paths) => paths,
+        Err(e) => {
+            println!("Failed to index book {}: {}", id, e);
+            return;
+        }
+    };
+    let id = id.to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = find_epub_in_dir(&book_dir)
+            .ok_or_else(|| "No epub file found".to_string())
+            .and_then(|epub_path| extract_book_terms(&epub_path))
+            .map(|(chapters, terms)| {
+                update_fulltext_index(&index_path, Some(&id), |index| {
+                    insert_into_index(index, &id, chapters, terms);
+                    true
+                })
+            });
+        if let Err(e) = result {
+            println!("Failed to index book {}: {}", id, e);
+        }
+    });
+}
+
+// 从全文索引中移除一本书（删除书籍时调用）
+pub fn remove_book_from_fulltext_index(app_handle: &AppHandle, id: &str) -> Result<(), String> {
+    let index_path = get_fulltext_index_path(app_handle)?;
+    update_fulltext_index(&index_path, Some(id), |index| remove_from_index(index, id));
+    Ok(())
+}
+
+// 提交重建全文索引的后台任务，从 books 目录重新提取所有书籍的文字
+// 重建期间导入或删除的书籍会记录下来，替换时以当前索引中的记录为准
+pub fn start_fulltext_rebuild_job(app_handle: &AppHandle) -> Result<String, String> {
+    let index_path = get_fulltext_index_path(app_handle)?;
+    let books_dir = get_books_dir(app_handle)?;
+    {
+        let mut state = FULLTEXT_STATE.lock().unwrap_or_else(|e| e.into_inner());
+        if state.rebuilding.is_some() {
+            return Err("Full-text index is already being rebuilt".to_string());
+        }
+        state.rebuilding = Some(HashSet::new());
+    }
+
+    spawn_job(app_handle, JobKind::IndexRebuild, move |job| async move {
+        let result = tokio::task::spawn_blocking(move || {
+            let hash_dirs: Vec<PathBuf> = fs::read_dir(&books_dir)
+                .map(|entries| {
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .map(|entry| entry.path())
+                        .filter(|path| path.is_dir())
+                        .collect()
+                })
+                .unwrap_or_default();
+            job.set_total(hash_dirs.len());
+
+            // 在新索引中重建，完成后再替换，重建过程中旧索引仍可搜索
+            let mut index = FullTextIndex {
+                version: FULLTEXT_INDEX_VERSION,
+                ..Default::default()
+            };
+            for hash_dir in hash_dirs {
+                if job.is_cancelled() {
+                    return Err("Rebuild cancelled".to_string());
+                }
+                let id = hash_dir
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                match find_epub_in_dir(&hash_dir).map(|epub_path| extract_book_terms(&epub_path)) {
+                    Some(Ok((chapters, terms))) => {
+                        insert_into_index(&mut index, &id, chapters, terms)
+                    }
+                    Some(Err(e)) => println!("Failed to index book {}: {}", id, e),
+                    None => {}
+                }
+                job.advance(Some(id));
+            }
+
+            let mut state = FULLTEXT_STATE.lock().unwrap_or_else(|e| e.into_inner());
+            let changed = state.rebuilding.take().unwrap_or_default();
+            let current = state
+                .index
+                .get_or_insert_with(|| read_fulltext_index(&index_path));
+            for id in changed {
+                copy_book_entry(current, &mut index, &id);
+            }
+            *current = index;
+            write_fulltext_index(&index_path, current)?;
+            Ok(serde_json::json!({ "indexed": current.books.len() }))
+        })
+        .await
+        .map_err(|e| format!("Rebuild task failed: {}", e));
+        // 取消或失败时不再记录变化
+        FULLTEXT_STATE
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .rebuilding = None;
+        result?
+    })
+}
+
+// 获取一个查询词的倒排记录，单个中文字符时合并所有包含该字的两字词
+fn postings_for(index: &FullTextIndex, token: &str) -> HashMap<(u32, u32), u32> {
+    let mut postings: HashMap<(u32, u32), u32> = HashMap::new();
+    let mut add = |list: &Vec<(u32, u32, u32)>| {
+        for &(key, spine_index, count) in list {
+            *postings.entry((key, spine_index)).or_default() += count;
+        }
+    };
+    let mut chars = token.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if is_cjk(c) => index
+            .terms
+            .iter()
+            .filter(|(term, _)| term.contains(c))
+            .for_each(|(_, list)| add(list)),
+        _ => {
+            if let Some(list) = index.terms.get(token) {
+                add(list);
+            }
+        }
+    }
+    postings
+}
+
+// 按 TF-IDF 对包含所有查询词的章节排序，返回 (书籍ID, 章节, 得分)
+fn rank_chapters(index: &FullTextIndex, tokens: &[String]) -> Vec<(String, usize, f64)> {
+    let total_chapters: u32 = index.books.values().map(|book| book.chapters).sum();
+    let mut scores: HashMap<(u32, u32), (usize, f64)> = HashMap::new();
+    for token in tokens {
+        let postings = postings_for(index, token);
+        if postings.is_empty() {
+            return Vec::new();
+        }
+        let idf = (1.0 + total_chapters as f64 / postings.len() as f64).ln();
+        for (chapter, count) in postings {
+            let entry = scores.entry(chapter).or_default();
+            entry.0 += 1;
+            entry.1 += (1.0 + (count as f64).ln()) * idf;
+        }
+    }
+
+    let mut ranked: Vec<(String, usize, f64)> = scores
+        .into_iter()
+        .filter(|(_, (matched, _))| *matched == tokens.len())
+        .filter_map(|((key, spine_index), (_, score))| {
+            let book = index.books.get(&key)?;
+            Some((book.id.clone(), spine_index as usize, score))
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.2.total_cmp(&a.2));
+    ranked
+}
+
+// 确认候选章节时依次尝试的查询：先匹配整个短语，其次是单个查询词
+// 倒排索引只要求章节包含所有词，不要求词的顺序和相邻
+fn location_queries(
+    query: &str,
+    tokens: &[String],
+) -> Result<Vec<(BookSearchQuery, Matcher)>, String> {
+    std::iter::once(query)
+        .chain(tokens.iter().map(|token| token.as_str()))
+        .map(|text| {
+            let query = BookSearchQuery {
+                query: text.to_string(),
+                ..Default::default()
+            };
+            let matcher = build_matcher(&query)?;
+            Ok((query, matcher))
+        })
+        .collect()
+}
+
+// 返回章节中第一个能匹配的查询的位置
+fn locate_in_chapter(
+    chapter: &ChapterText,
+    label: Option<String>,
+    queries: &[(BookSearchQuery, Matcher)],
+) -> Option<SearchMatch> {
+    queries.iter().find_map(|(query, matcher)| {
+        search_chapter(chapter, label.clone(), query, matcher, 1).pop()
+    })
+}
+
+// 在整个书库中搜索，返回按相关度排序的书籍和位置
+// 倒排索引只记录词频，候选章节会重新读取正文确认匹配并生成片段和CFI
+pub async fn search_fulltext(
+    app_handle: &AppHandle,
+    query: &str,
+    limit: Option<usize>,
+) -> Result<Vec<FullTextHit>, String> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = tokenize(&chars);
+    tokens.sort();
+    tokens.dedup();
+    if tokens.is_empty() {
+        return Err("Search query is empty".to_string());
+    }
+    let limit = limit.unwrap_or(DEFAULT_FULLTEXT_LIMIT);
+    let index_path = get_fulltext_index_path(app_handle)?;
+    let books_dir = get_books_dir(app_handle)?;
+    let query = query.to_string();
+
+    let hits = tokio::task::spawn_blocking(move || {
+        let mut ranked = Vec::new();
+        update_fulltext_index(&index_path, None, |index| {
+            ranked = rank_chapters(index, &tokens);
+            // 越少见的词越可能定位到相关的段落
+            tokens.sort_by_cached_key(|token| postings_for(index, token).len());
+            false
+        });
+        let queries = location_queries(&query, &tokens)?;
+
+        let mut hits = Vec::new();
+        let mut open_book: Option<(String, EpubDoc<_>, usize, Vec<_>)> = None;
+        for (book_id, spine_index, score) in ranked {
+            if hits.len() >= limit {
+                break;
+            }
+            if open_book.as_ref().map(|(id, _, _, _)| id) != Some(&book_id) {
+                let hash_dir = books_dir.join(&book_id);
+                let Some(mut doc) = find_epub_in_dir(&hash_dir).and_then(|p| EpubDoc::new(p).ok())
+                else {
+                    continue;
+                };
+                let spine_element = spine_element_step(&mut doc);
+                let toc = load_book_navigation(&hash_dir)
+                    .map(|navigation| navigation.toc)
+                    .unwrap_or_default();
+                open_book = Some((book_id.clone(), doc, spine_element, toc));
+            }
+            let Some((_, doc, spine_element, toc)) = open_book.as_mut() else {
+                continue;
+            };
+            let Some(chapter) = read_chapter_text(doc, *spine_element, spine_index) else {
+                continue;
+            };
+            let label = chapter_label(toc, spine_index);
+            if let Some(location) = locate_in_chapter(&chapter, label, &queries) {
+                hits.push(FullTextHit {
+                    book_id,
+                    title: None,
+                    score,
+                    location,
+                });
+            }
+        }
+        Ok::<_, String>(hits)
+    })
+    .await
+    .map_err(|e| format!("Search task failed: {}", e))??;
+
+    // 补充书名
+    Ok(hits
+        .into_iter()
+        .map(|mut hit| {
+            hit.title = get_library_record(app_handle, &hit.book_id)
+                .ok()
+                .flatten()
+                .and_then(|record| record.metadata.title);
+            hit
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::extract_document_text;
+
+    fn tokens(text: &str) -> Vec<String> {
+        tokenize(&text.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn tokenize_lowercases_words_and_strips_diacritics() {
+        assert_eq!(tokens("Café, NAÏVE e\u{0301}t"), ["cafe", "naive", "et"]);
+    }
+
+    #[test]
+    fn tokenize_splits_cjk_runs_into_bigrams() {
+        assert_eq!(tokens("阅读器abc书"), ["阅读", "读器", "abc", "书"]);
+    }
+
+    #[test]
+    fn tokenize_drops_overlong_words() {
+        let long = "a".repeat(MAX_TERM_CHARS + 1);
+        assert_eq!(tokens(&format!("{} ok", long)), ["ok"]);
+    }
+
+    fn chapter(html: &str) -> ChapterText {
+        ChapterText {
+            spine_index: 0,
+            href: "ch1.xhtml".to_string(),
+            spine_step: "/6/2".to_string(),
+            text: extract_document_text(html),
+        }
+    }
+
+    fn locate(html: &str, query: &str) -> Option<String> {
+        let queries = location_queries(query, &tokens(query)).unwrap();
+        locate_in_chapter(&chapter(html), None, &queries).map(|location| location.matched)
+    }
+
+    #[test]
+    fn locate_prefers_the_whole_phrase() {
+        let html = "<html><body><p>Hello there. Hello world!</p></body></html>";
+        assert_eq!(locate(html, "hello world").as_deref(), Some("Hello world"));
+    }
+
+    #[test]
+    fn locate_falls_back_to_single_tokens() {
+        let html = "<html><body><p>The world said hello.</p></body></html>";
+        assert_eq!(locate(html, "hello world").as_deref(), Some("hello"));
+        assert_eq!(locate(html, "hello, world").as_deref(), Some("hello"));
+        assert_eq!(locate(html, "missing").as_deref(), None);
+    }
+}
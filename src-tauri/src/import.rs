@@ -0,0 +1,205 @@
+use crate::file::import_epub_file;
+use crate::jobs::{spawn_job, JobHandle};
+use crate::model::{ImportResult, ImportStatus, JobKind};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::AppHandle;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+// 扫描文件夹的结果
+#[derive(Default)]
+struct ScanResult {
+    files: Vec<PathBuf>,
+    failures: Vec<ImportResult>, // Paths that could not be read
+    visited: HashSet<PathBuf>,   // Canonical paths of scanned directories
+}
+
+fn failed_result(path: &Path, error: String) -> ImportResult {
+    ImportResult {
+        origin_path: path.to_string_lossy().to_string(),
+        status: ImportStatus::Failed,
+        book: None,
+        error: Some(error),
+    }
+}
+
+// 递归收集目录下所有的epub文件
+// 按真实路径记录已扫描的目录，避免符号链接形成循环；无法读取的目录记为失败，继续扫描其他目录
+fn collect_epub_files(dir: &Path, scan: &mut ScanResult) {
+    let entries = match dir.canonicalize().and_then(|real_dir| {
+        if scan.visited.insert(real_dir) {
+            std::fs::read_dir(dir).map(Some)
+        } else {
+            Ok(None)
+        }
+    }) {
+        Ok(Some(entries)) => entries,
+        Ok(None) => return,
+        Err(e) => {
+            let error = format!("Failed to read directory {}: {}", dir.display(), e);
+            scan.failures.push(failed_result(dir, error));
+            return;
+        }
+    };
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                let error = format!("Failed to read directory entry: {}", e);
+                scan.failures.push(failed_result(dir, error));
+                continue;
+            }
+        };
+        let path = entry.path();
+        if path.is_dir() {
+            collect_epub_files(&path, scan);
+        } else if path
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("epub"))
+            .unwrap_or(false)
+        {
+            files.push(path);
+        }
+    }
+    scan.files.extend(files);
+}
+
+// 递归查找文件夹中的所有epub文件，返回排序后的文件和无法读取的路径
+pub fn scan_epub_files(dir: &Path) -> (Vec<PathBuf>, Vec<ImportResult>) {
+    let mut scan = ScanResult::default();
+    collect_epub_files(dir, &mut scan);
+    scan.files.sort();
+    (scan.files, scan.failures)
+}
+
+// 在阻塞线程池中展开要导入的路径：文件夹递归查找epub文件，其他路径按文件导入
+async fn scan_import_paths(
+    paths: Vec<PathBuf>,
+) -> Result<(Vec<PathBuf>, Vec<ImportResult>), String> {
+    tokio::task::spawn_blocking(move || {
+        let mut files = Vec::new();
+        let mut failures = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                let (dir_files, dir_failures) = scan_epub_files(&path);
+                files.extend(dir_files);
+                failures.extend(dir_failures);
+            } else {
+                files.push(path);
+            }
+        }
+        (files, failures)
+    })
+    .await
+    .map_err(|e| format!("Scan task failed: {}", e))
+}
+
+// 递归导入文件夹中的所有epub文件
+// 在tokio运行时上并发导入，返回每个文件的导入报告（顺序与扫描顺序一致）
+pub async fn import_epub_directory(
+    app_handle: &AppHandle,
+    dir_path: &str,
+) -> Result<Vec<ImportResult>, String> {
+    let dir = Path::new(dir_path);
+    if !dir.is_dir() {
+        return Err(format!("Not a directory: {}", dir_path));
+    }
+
+    let (files, failures) = scan_import_paths(vec![dir.to_path_buf()]).await?;
+
+    // 无法读取的目录排在最后
+    let mut results = import_epub_files(app_handle, files, None).await?;
+    results.extend(failures);
+    Ok(results)
+}
+
+// 并发导入多个epub文件，返回每个文件的导入报告（顺序与传入顺序一致）
+// 传入任务句柄时汇报进度，任务被取消后尚未开始的文件标记为失败
+pub async fn import_epub_files(
+    app_handle: &AppHandle,
+    files: Vec<PathBuf>,
+    job: Option<JobHandle>,
+) -> Result<Vec<ImportResult>, String> {
+    if let Some(job) = &job {
+        job.set_total(files.len());
+    }
+
+    // 限制同时导入的文件数量，避免同时打开过多文件
+    let concurrency = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4);
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+
+    let mut tasks = JoinSet::new();
+    for (index, file) in files.iter().enumerate() {
+        let app_handle = app_handle.clone();
+        let semaphore = semaphore.clone();
+        let job = job.clone();
+        let file = file.clone();
+        let origin_path = file.to_string_lossy().to_string();
+        tasks.spawn(async move {
+            // 信号量不会被关闭，获取许可不会失败
+            let _permit = semaphore.acquire_owned().await.ok();
+            if job.as_ref().is_some_and(|job| job.is_cancelled()) {
+                let result = ImportResult {
+                    origin_path,
+                    status: ImportStatus::Failed,
+                    book: None,
+                    error: Some("Cancelled".to_string()),
+                };
+                return (index, result);
+            }
+            let result = match import_epub_file(&app_handle, &file).await {
+                Ok((book, true)) => ImportResult {
+                    origin_path,
+                    status: ImportStatus::Imported,
+                    book: Some(book),
+                    error: None,
+                },
+                Ok((book, false)) => ImportResult {
+                    origin_path,
+                    status: ImportStatus::AlreadyPresent,
+                    book: Some(book),
+                    error: None,
+                },
+                Err(e) => ImportResult {
+                    origin_path,
+                    status: ImportStatus::Failed,
+                    book: None,
+                    error: Some(e),
+                },
+            };
+            if let Some(job) = &job {
+                job.advance(Some(result.origin_path.clone()));
+            }
+            (index, result)
+        });
+    }
+
+    let mut results: Vec<Option<ImportResult>> = vec![None; files.len()];
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((index, result)) => results[index] = Some(result),
+            Err(e) => return Err(format!("Import task failed: {}", e)),
+        }
+    }
+
+    Ok(results.into_iter().flatten().collect())
+}
+
+// 提交后台导入任务，paths 可以是epub文件或文件夹（递归导入）
+// 返回任务ID，完成后任务结果为每个文件的导入报告
+pub fn start_import_job(app_handle: &AppHandle, paths: Vec<String>) -> Result<String, String> {
+    let paths = paths.into_iter().map(PathBuf::from).collect();
+    let handle = app_handle.clone();
+    spawn_job(app_handle, JobKind::Import, move |job| async move {
+        let (files, failures) = scan_import_paths(paths).await?;
+        let mut results = import_epub_files(&handle, files, Some(job)).await?;
+        results.extend(failures);
+        serde_json::to_value(results).map_err(|e| format!("Failed to serialize results: {}", e))
+    })
+}
@@ -0,0 +1,382 @@
+use crate::cover::find_cover_file;
+use crate::file::{
+    find_epub_in_dir, get_current_timestamp, hash_file_sha256, is_book_importing, is_legacy_md5_id,
+};
+use crate::jobs::{spawn_job, JobHandle};
+use crate::library::{
+    build_library_record, get_books_dir, reconcile_library_index, upsert_library_record,
+};
+use crate::model::{
+    BookMark, BookMetadata, BookNavigation, CollectionStore, IntegrityIssue, IntegrityIssueKind,
+    IntegrityReport, JobKind, LibraryIndex, ReaderStyle, ReadingProgress, ReadingSession,
+    SmartShelfStore, TxtImportSettings, WatchFolderConfig,
+};
+use crate::navigation::NAVIGATION_FILE;
+use crate::progress::PROGRESS_FILE;
+use crate::session::SESSIONS_FILE;
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::AppHandle;
+use tauri::Manager;
+
+// 最近修改过的书籍目录可能还在写入，不做检查
+const RECENT_WRITE_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+// 检查并修复书库时用到的路径
+struct IntegrityContext {
+    app_dir: PathBuf,
+    books_dir: PathBuf,
+    quarantine_dir: PathBuf, // /com.rbook.app/quarantine/<timestamp>
+    repair: bool,
+}
+
+impl IntegrityContext {
+    // 将损坏的文件或目录移动到隔离目录，保留相对于应用目录的路径
+    fn quarantine(&self, path: &Path) -> Result<(), String> {
+        let relative = path.strip_prefix(&self.app_dir).unwrap_or(path);
+        let dest = self.quarantine_dir.join(relative);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create quarantine directory: {}", e))?;
+        }
+        fs::rename(path, &dest).map_err(|e| format!("Failed to quarantine file: {}", e))
+    }
+}
+
+// 文件校验函数，返回错误信息
+type FileValidator = fn(&Path) -> Option<String>;
+
+// 校验JSON文件能否解析为对应的结构，返回错误信息
+fn validate_json<T: DeserializeOwned>(path: &Path) -> Option<String> {
+    let json_data = match fs::read_to_string(path) {
+        Ok(json_data) => json_data,
+        Err(e) => return Some(format!("Failed to read file: {}", e)),
+    };
+    serde_json::from_str::<T>(&json_data)
+        .err()
+        .map(|e| format!("Invalid JSON: {}", e))
+}
+
+// 记录一个问题，修复失败时把原因附加到说明中
+fn push_issue(
+    report: &mut IntegrityReport,
+    kind: IntegrityIssueKind,
+    book_id: Option<&str>,
+    path: &Path,
+    detail: String,
+    repair_result: Option<Result<(), String>>,
+) {
+    let (repaired, detail) = match repair_result {
+        Some(Ok(())) => (true, detail),
+        Some(Err(e)) => (false, format!("{} (repair failed: {})", detail, e)),
+        None => (false, detail),
+    };
+    report.issues.push(IntegrityIssue {
+        kind,
+        book_id: book_id.map(|id| id.to_string()),
+        path: path.to_string_lossy().to_string(),
+        detail,
+        repaired,
+    });
+}
+
+// 校验配置目录下的JSON文件，损坏的文件隔离后由各模块按默认值重新生成
+fn check_config_files(ctx: &IntegrityContext, report: &mut IntegrityReport) {
+    let config_dir = ctx.app_dir.join("config");
+    let checks: [(&str, FileValidator); 6] = [
+        ("library.json", validate_json::<LibraryIndex>),
+        ("collections.json", validate_json::<CollectionStore>),
+        ("smart_shelves.json", validate_json::<SmartShelfStore>),
+        ("reader_style.json", validate_json::<ReaderStyle>),
+        ("watch_folders.json", validate_json::<WatchFolderConfig>),
+        ("txt_import.json", validate_json::<TxtImportSettings>),
+    ];
+    for (file_name, validate) in checks {
+        let path = config_dir.join(file_name);
+        if !path.exists() {
+            continue;
+        }
+        if let Some(detail) = validate(&path) {
+            let repair_result = ctx.repair.then(|| ctx.quarantine(&path));
+            push_issue(
+                report,
+                IntegrityIssueKind::CorruptFile,
+                None,
+                &path,
+                detail,
+                repair_result,
+            );
+        }
+    }
+}
+
+// 目录本身或其中的文件是否在最近一段时间内修改过
+fn recently_modified(dir: &Path) -> bool {
+    let entries = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+        })
+        .into_iter()
+        .flatten();
+    std::iter::once(dir.to_path_buf())
+        .chain(entries)
+        .filter_map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .any(|modified| {
+            SystemTime::now()
+                .duration_since(modified)
+                .map_or(true, |age| age < RECENT_WRITE_WINDOW)
+        })
+}
+
+// 检查单本书的目录，返回是否需要重新生成封面和索引记录
+fn check_book_dir(
+    ctx: &IntegrityContext,
+    hash_dir: &Path,
+    id: &str,
+    report: &mut IntegrityReport,
+) -> Option<PathBuf> {
+    // 旧版md5目录由后台迁移任务处理，迁移可能还没完成或失败后保留了原目录
+    if is_legacy_md5_id(id) {
+        report.pending_migration.push(id.to_string());
+        return None;
+    }
+
+    // 目录名不是SHA-256哈希
+    if id.len() != 64 || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        let repair_result = ctx.repair.then(|| ctx.quarantine(hash_dir));
+        push_issue(
+            report,
+            IntegrityIssueKind::InvalidDirectory,
+            Some(id),
+            hash_dir,
+            "Directory name is not a book ID".to_string(),
+            repair_result,
+        );
+        return None;
+    }
+
+    // 正在导入的书籍目录中epub文件可能还没有复制完
+    if is_book_importing(id) || recently_modified(hash_dir) {
+        report.skipped_books.push(id.to_string());
+        return None;
+    }
+
+    // 导入中断时可能留下没有epub文件的目录，隔离而不是直接删除
+    let Some(epub_path) = find_epub_in_dir(hash_dir) else {
+        let repair_result = ctx.repair.then(|| ctx.quarantine(hash_dir));
+        push_issue(
+            report,
+            IntegrityIssueKind::MissingEpub,
+            Some(id),
+            hash_dir,
+            "Book directory has no EPUB file".to_string(),
+            repair_result,
+        );
+        return None;
+    };
+
+    // 复制中断的epub文件哈希值与目录名不一致，整个目录隔离，需要重新导入
+    match hash_file_sha256(&epub_path) {
+        Ok(hash) if hash == id => {}
+        result => {
+            let detail = match result {
+                Ok(hash) => format!("EPUB hash {} does not match directory name", hash),
+                Err(e) => e,
+            };
+            let repair_result = ctx.repair.then(|| ctx.quarantine(hash_dir));
+            push_issue(
+                report,
+                IntegrityIssueKind::HashMismatch,
+                Some(id),
+                &epub_path,
+                detail,
+                repair_result,
+            );
+            return None;
+        }
+    }
+
+    // 书签、阅读进度、阅读记录、目录缓存、元数据和最后打开时间文件
+    let checks: [(&str, FileValidator); 6] = [
+        ("mark.json", validate_json::<BookMark>),
+        (PROGRESS_FILE, validate_json::<ReadingProgress>),
+        (SESSIONS_FILE, validate_json::<Vec<ReadingSession>>),
+        (NAVIGATION_FILE, validate_json::<BookNavigation>),
+        ("metadata.json", validate_json::<BookMetadata>),
+        (".lastopened", |path| {
+            let content = fs::read_to_string(path).unwrap_or_default();
+            content
+                .trim()
+                .parse::<u64>()
+                .err()
+                .map(|e| format!("Invalid timestamp: {}", e))
+        }),
+    ];
+    for (file_name, validate) in checks {
+        let path = hash_dir.join(file_name);
+        if !path.exists() {
+            continue;
+        }
+        if let Some(detail) = validate(&path) {
+            let repair_result = ctx.repair.then(|| ctx.quarantine(&path));
+            push_issue(
+                report,
+                IntegrityIssueKind::CorruptFile,
+                Some(id),
+                &path,
+                detail,
+                repair_result,
+            );
+        }
+    }
+
+    if find_cover_file(hash_dir).is_none() {
+        push_issue(
+            report,
+            IntegrityIssueKind::MissingCover,
+            Some(id),
+            hash_dir,
+            "Cover image is missing".to_string(),
+            None,
+        );
+        return Some(epub_path);
+    }
+    None
+}
+
+// 扫描书库：校验每本书的哈希值和JSON文件，repair 为 true 时同时修复
+// 修复方式：重新生成封面、隔离损坏的文件和没有epub文件的目录
+// 正在导入或最近修改过的书籍目录会跳过
+fn check_library_integrity(
+    app_handle: &AppHandle,
+    ctx: &IntegrityContext,
+    job: &JobHandle,
+) -> Result<IntegrityReport, String> {
+    let mut report = IntegrityReport::default();
+    check_config_files(ctx, &mut report);
+
+    let hash_dirs: Vec<PathBuf> = match fs::read_dir(&ctx.books_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    job.set_total(hash_dirs.len());
+
+    for hash_dir in hash_dirs {
+        if job.is_cancelled() {
+            break;
+        }
+        let id = hash_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        report.checked_books += 1;
+
+        if let Some(epub_path) = check_book_dir(ctx, &hash_dir, &id, &mut report) {
+            if ctx.repair {
+                // 重新生成封面并更新索引记录
+                let repair_result = build_library_record(&hash_dir, &epub_path)
+                    .and_then(|record| upsert_library_record(app_handle, record));
+                if let Some(issue) = report.issues.last_mut() {
+                    match repair_result {
+                        Ok(()) => issue.repaired = true,
+                        Err(e) => issue.detail = format!("{} (repair failed: {})", issue.detail, e),
+                    }
+                }
+            }
+        }
+        job.advance(Some(id));
+    }
+
+    if ctx.repair && report.issues.iter().any(|issue| issue.repaired) {
+        report.quarantine_dir = ctx
+            .quarantine_dir
+            .exists()
+            .then(|| ctx.quarantine_dir.to_string_lossy().to_string());
+    }
+    Ok(report)
+}
+
+// 提交后台书库检查任务，返回任务ID，完成后任务结果为检查报告
+pub fn start_integrity_check_job(app_handle: &AppHandle, repair: bool) -> Result<String, String> {
+    let app_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+    let ctx = IntegrityContext {
+        books_dir: get_books_dir(app_handle)?,
+        quarantine_dir: app_dir
+            .join("quarantine")
+            .join(get_current_timestamp()?.to_string()),
+        app_dir,
+        repair,
+    };
+    let handle = app_handle.clone();
+
+    spawn_job(app_handle, JobKind::IntegrityCheck, move |job| async move {
+        let app_handle = handle.clone();
+        let report =
+            tokio::task::spawn_blocking(move || check_library_integrity(&app_handle, &ctx, &job))
+                .await
+                .map_err(|e| format!("Integrity check task failed: {}", e))??;
+        // 移除已删除或隔离书籍的索引记录
+        if report.issues.iter().any(|issue| issue.repaired) {
+            reconcile_library_index(&handle).await?;
+        }
+        serde_json::to_value(report).map_err(|e| format!("Failed to serialize report: {}", e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_context(name: &str) -> IntegrityContext {
+        let app_dir = std::env::temp_dir().join(format!("rbook-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&app_dir);
+        IntegrityContext {
+            books_dir: app_dir.join("books"),
+            quarantine_dir: app_dir.join("quarantine").join("test"),
+            app_dir,
+            repair: true,
+        }
+    }
+
+    #[test]
+    fn legacy_book_dirs_are_left_for_migration() {
+        let ctx = test_context("integrity-legacy");
+        let id = "0123456789abcdef0123456789abcdef";
+        let hash_dir = ctx.books_dir.join(id);
+        fs::create_dir_all(&hash_dir).unwrap();
+        fs::write(hash_dir.join("book.epub"), b"epub").unwrap();
+
+        let mut report = IntegrityReport::default();
+        assert!(check_book_dir(&ctx, &hash_dir, id, &mut report).is_none());
+        assert_eq!(report.pending_migration, [id]);
+        assert!(report.issues.is_empty());
+        assert!(hash_dir.join("book.epub").exists());
+        fs::remove_dir_all(&ctx.app_dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_dirs_are_quarantined() {
+        let ctx = test_context("integrity-unknown");
+        let hash_dir = ctx.books_dir.join("not-a-book");
+        fs::create_dir_all(&hash_dir).unwrap();
+
+        let mut report = IntegrityReport::default();
+        assert!(check_book_dir(&ctx, &hash_dir, "not-a-book", &mut report).is_none());
+        assert_eq!(report.issues.len(), 1);
+        assert!(report.issues[0].repaired);
+        assert!(!hash_dir.exists());
+        assert!(ctx.quarantine_dir.join("books/not-a-book").exists());
+        fs::remove_dir_all(&ctx.app_dir).unwrap();
+    }
+}
@@ -0,0 +1,220 @@
+use crate::file::get_current_timestamp;
+use crate::model::{JobInfo, JobKind, JobStatus};
+use rand::Rng;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Semaphore;
+
+// 任务状态变化时发送给前端的事件
+pub const JOB_PROGRESS_EVENT: &str = "job-progress";
+
+// 同时运行的后台任务数量，其余任务排队等待
+const MAX_RUNNING_JOBS: usize = 2;
+
+// 书内搜索等用户正在等待结果的任务使用单独的名额，不会排在导入、重建索引等长时间任务后面
+const MAX_INTERACTIVE_JOBS: usize = 2;
+
+// 保留的已结束任务数量，超出时移除最早结束的任务
+const MAX_FINISHED_JOBS: usize = 50;
+
+// 后台任务
+struct JobEntry {
+    info: JobInfo,
+    cancelled: Arc<AtomicBool>,
+}
+
+// 后台任务队列的状态
+pub struct JobState {
+    jobs: Mutex<HashMap<String, JobEntry>>,
+    slots: Arc<Semaphore>,
+    interactive_slots: Arc<Semaphore>,
+}
+
+impl Default for JobState {
+    fn default() -> Self {
+        JobState {
+            jobs: Mutex::new(HashMap::new()),
+            slots: Arc::new(Semaphore::new(MAX_RUNNING_JOBS)),
+            interactive_slots: Arc::new(Semaphore::new(MAX_INTERACTIVE_JOBS)),
+        }
+    }
+}
+
+// 传给任务执行体的句柄，用于汇报进度和检查是否被取消
+#[derive(Clone)]
+pub struct JobHandle {
+    app_handle: AppHandle,
+    id: String,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl JobHandle {
+    // 任务ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    // 任务是否已被取消，执行体应在处理每一项前检查
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // 设置任务的总步数
+    pub fn set_total(&self, total: usize) {
+        update_job(&self.app_handle, &self.id, |info| info.total = total);
+    }
+
+    // 完成一步，message 为当前处理的项目
+    pub fn advance(&self, message: Option<String>) {
+        update_job(&self.app_handle, &self.id, |info| {
+            info.completed += 1;
+            info.message = message;
+        });
+    }
+}
+
+// 修改任务信息并通知前端
+fn update_job(app_handle: &AppHandle, id: &str, f: impl FnOnce(&mut JobInfo)) {
+    let state = app_handle.state::<JobState>();
+    let info = {
+        let mut jobs = state.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(entry) = jobs.get_mut(id) else {
+            return;
+        };
+        f(&mut entry.info);
+        entry.info.clone()
+    };
+    let _ = app_handle.emit(JOB_PROGRESS_EVENT, info);
+}
+
+// 提交后台任务，返回任务ID
+// 执行体返回的结果会保存在任务信息中，可通过 get_job 查询
+pub fn spawn_job<F, Fut>(app_handle: &AppHandle, kind: JobKind, f: F) -> Result<String, String>
+where
+    F: FnOnce(JobHandle) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<serde_json::Value, String>> + Send + 'static,
+{
+    let id = format!("{:016x}", rand::thread_rng().gen::<u64>());
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let info = JobInfo {
+        id: id.clone(),
+        kind,
+        status: JobStatus::Queued,
+        total: 0,
+        completed: 0,
+        message: None,
+        error: None,
+        result: None,
+        created_at: get_current_timestamp()?,
+        finished_at: None,
+    };
+
+    let state = app_handle.state::<JobState>();
+    state.jobs.lock().unwrap_or_else(|e| e.into_inner()).insert(
+        id.clone(),
+        JobEntry {
+            info: info.clone(),
+            cancelled: cancelled.clone(),
+        },
+    );
+    let _ = app_handle.emit(JOB_PROGRESS_EVENT, info);
+
+    let slots = match kind {
+        JobKind::Search => state.interactive_slots.clone(),
+        _ => state.slots.clone(),
+    };
+    let handle = JobHandle {
+        app_handle: app_handle.clone(),
+        id: id.clone(),
+        cancelled,
+    };
+    tauri::async_runtime::spawn(async move {
+        // 信号量不会被关闭，获取许可不会失败
+        let _permit = slots.acquire_owned().await.ok();
+        let app_handle = handle.app_handle.clone();
+        let id = handle.id.clone();
+        if handle.is_cancelled() {
+            finish_job(&app_handle, &id, JobStatus::Cancelled, None, None);
+            return;
+        }
+
+        update_job(&app_handle, &id, |info| info.status = JobStatus::Running);
+        let cancelled = handle.cancelled.clone();
+        // 取消后执行体仍返回结果时保留已完成部分的结果
+        match (f(handle).await, cancelled.load(Ordering::Relaxed)) {
+            (Ok(result), true) => {
+                finish_job(&app_handle, &id, JobStatus::Cancelled, Some(result), None)
+            }
+            (Err(_), true) => finish_job(&app_handle, &id, JobStatus::Cancelled, None, None),
+            (Ok(result), false) => {
+                finish_job(&app_handle, &id, JobStatus::Completed, Some(result), None)
+            }
+            (Err(e), false) => finish_job(&app_handle, &id, JobStatus::Failed, None, Some(e)),
+        }
+    });
+
+    Ok(id)
+}
+
+// 标记任务结束
+fn finish_job(
+    app_handle: &AppHandle,
+    id: &str,
+    status: JobStatus,
+    result: Option<serde_json::Value>,
+    error: Option<String>,
+) {
+    let finished_at = get_current_timestamp().ok();
+    update_job(app_handle, id, |info| {
+        info.status = status;
+        info.result = result;
+        info.error = error;
+        info.finished_at = finished_at;
+    });
+    prune_finished_jobs(&app_handle.state::<JobState>());
+}
+
+// 移除超出数量的已结束任务，结束状态已通过事件通知前端
+fn prune_finished_jobs(state: &JobState) {
+    let mut jobs = state.jobs.lock().unwrap_or_else(|e| e.into_inner());
+    let mut finished: Vec<(u64, String)> = jobs
+        .values()
+        .filter_map(|entry| Some((entry.info.finished_at?, entry.info.id.clone())))
+        .collect();
+    if finished.len() <= MAX_FINISHED_JOBS {
+        return;
+    }
+    finished.sort();
+    for (_, id) in finished.iter().take(finished.len() - MAX_FINISHED_JOBS) {
+        jobs.remove(id);
+    }
+}
+
+// 取消任务，排队中的任务不会再执行，运行中的任务在处理下一项前停止
+pub async fn cancel_job(state: State<'_, JobState>, id: &str) -> Result<(), String> {
+    let jobs = state.jobs.lock().unwrap_or_else(|e| e.into_inner());
+    let entry = jobs
+        .get(id)
+        .ok_or_else(|| format!("Job not found: {}", id))?;
+    entry.cancelled.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+// 查询任务状态
+pub async fn get_job(state: State<'_, JobState>, id: &str) -> Result<JobInfo, String> {
+    let jobs = state.jobs.lock().unwrap_or_else(|e| e.into_inner());
+    jobs.get(id)
+        .map(|entry| entry.info.clone())
+        .ok_or_else(|| format!("Job not found: {}", id))
+}
+
+// 获取所有任务，按创建时间排序
+pub async fn list_jobs(state: State<'_, JobState>) -> Result<Vec<JobInfo>, String> {
+    let jobs = state.jobs.lock().unwrap_or_else(|e| e.into_inner());
+    let mut list: Vec<JobInfo> = jobs.values().map(|entry| entry.info.clone()).collect();
+    list.sort_by_key(|info| info.created_at);
+    Ok(list)
+}
@@ -1,22 +1,80 @@
+mod backup;
+mod collection;
 mod cover;
+mod custom_cover;
+mod duplicate;
+mod epub_builder;
 mod file;
+mod fulltext;
+mod import;
+mod integrity;
+mod jobs;
+mod library;
 mod mark;
+mod markdown;
+mod metadata;
 mod model;
+mod navigation;
+mod placeholder;
+mod progress;
+mod protocol;
+mod restore;
+mod search;
+mod session;
+mod smart_shelf;
 mod style;
+mod trash;
 mod tray;
+mod txt;
+mod util;
+mod watch;
 
-use cover::init_default_cover;
+use backup::start_export_job;
+use collection::{
+    add_book_to_collection, create_collection, delete_collection, load_collection_books,
+    load_collections, remove_book_from_collection, rename_collection, reorder_collection,
+};
+use custom_cover::{
+    list_epub_images, revert_custom_cover, set_custom_cover_from_epub, set_custom_cover_from_file,
+};
+use duplicate::{find_duplicate_books, merge_duplicate_books};
 use file::{
-    load_all_local_epub_files, read_epub_file_content, save_file_and_return_local_path,
+    load_all_local_epub_files, save_file_and_return_local_path, start_migration_job,
     update_last_opened,
 };
+use fulltext::{search_fulltext, start_fulltext_rebuild_job};
+use import::{import_epub_directory, start_import_job};
+use integrity::start_integrity_check_job;
+use jobs::{cancel_job, get_job, list_jobs, JobState};
+use library::{query_library, start_reindex_job};
 use mark::{load_bookmark_from_local_storage, save_bookmark_to_local_storage};
-use model::{BookMark, EpubFile, ReaderStyle};
+use model::{
+    BookMark, BookNavigation, BookSearchQuery, Collection, DuplicateGroup, EpubFile, EpubImage,
+    FullTextHit, ImportResult, JobInfo, LibraryPage, LibraryQuery, MergeStrategy, ReaderStyle,
+    ReadingProgress, ReadingSession, ReadingStats, ReadingStatsQuery, SmartRule, SmartShelf,
+    TrashedBook, TxtImportSettings,
+};
+use navigation::get_book_navigation;
+use progress::{get_reading_progress, save_reading_progress};
+use protocol::{book_url, handle_book_protocol, BOOK_PROTOCOL};
+use restore::start_restore_job;
+use search::start_book_search;
+use session::{
+    get_reading_stats, load_reading_sessions, start_reading_session, update_reading_session,
+};
+use smart_shelf::{
+    delete_smart_shelf, evaluate_smart_rule, evaluate_smart_shelf, load_smart_shelves,
+    save_smart_shelf,
+};
 use style::{load_style_from_local_storage, save_style_to_local_storage};
-use tauri::path::BaseDirectory;
 use tauri::AppHandle;
-use tauri::Manager;
+use tauri::State;
+use trash::{delete_book, load_trashed_books, purge_trash, restore_book};
 use tray::setup_tray;
+use txt::{load_txt_import_settings, save_txt_import_settings};
+use watch::{
+    add_watch_folder, init_watch_folders, load_watch_folders, remove_watch_folder, WatchState,
+};
 
 // 保存文件并返回本地路径
 #[tauri::command]
@@ -27,16 +85,337 @@ async fn save_file_and_return_local_path_command(
     save_file_and_return_local_path(&app_handle, &origin_path).await
 }
 
+// 递归导入文件夹中的所有epub文件
+#[tauri::command]
+async fn import_epub_directory_command(
+    app_handle: AppHandle,
+    dir_path: String,
+) -> Result<Vec<ImportResult>, String> {
+    import_epub_directory(&app_handle, &dir_path).await
+}
+
+// 提交后台导入任务，paths可以是epub文件或文件夹，返回任务ID
+#[tauri::command]
+async fn start_import_job_command(
+    app_handle: AppHandle,
+    paths: Vec<String>,
+) -> Result<String, String> {
+    start_import_job(&app_handle, paths)
+}
+
+// 提交后台重建书库索引任务，返回任务ID
+#[tauri::command]
+async fn start_reindex_job_command(app_handle: AppHandle) -> Result<String, String> {
+    start_reindex_job(&app_handle)
+}
+
+// 提交后台导出备份任务，include_books为false时只备份笔记数据，返回任务ID
+#[tauri::command]
+async fn start_export_job_command(
+    app_handle: AppHandle,
+    dest_path: String,
+    include_books: bool,
+) -> Result<String, String> {
+    start_export_job(&app_handle, &dest_path, include_books)
+}
+
+// 提交后台恢复备份任务，返回任务ID
+#[tauri::command]
+async fn start_restore_job_command(
+    app_handle: AppHandle,
+    archive_path: String,
+    strategy: MergeStrategy,
+) -> Result<String, String> {
+    start_restore_job(&app_handle, &archive_path, strategy)
+}
+
+// 提交后台书库检查任务，repair 为 true 时同时修复发现的问题
+#[tauri::command]
+async fn start_integrity_check_job_command(
+    app_handle: AppHandle,
+    repair: bool,
+) -> Result<String, String> {
+    start_integrity_check_job(&app_handle, repair)
+}
+
+// 取消后台任务
+#[tauri::command]
+async fn cancel_job_command(state: State<'_, JobState>, job_id: String) -> Result<(), String> {
+    cancel_job(state, &job_id).await
+}
+
+// 查询后台任务状态
+#[tauri::command]
+async fn get_job_command(state: State<'_, JobState>, job_id: String) -> Result<JobInfo, String> {
+    get_job(state, &job_id).await
+}
+
+// 获取所有后台任务
+#[tauri::command]
+async fn list_jobs_command(state: State<'_, JobState>) -> Result<Vec<JobInfo>, String> {
+    list_jobs(state).await
+}
+
 // 加载本地epub文件
 #[tauri::command]
 async fn load_all_local_epub_files_command(app_handle: AppHandle) -> Result<Vec<EpubFile>, String> {
     load_all_local_epub_files(&app_handle).await
 }
 
-// 读取epub文件内容
+// 分页查询书库，支持排序和过滤
+#[tauri::command]
+async fn query_library_command(
+    app_handle: AppHandle,
+    query: LibraryQuery,
+) -> Result<LibraryPage, String> {
+    query_library(&app_handle, &query).await
+}
+
+// 将书籍移动到回收站
+#[tauri::command]
+async fn delete_book_command(app_handle: AppHandle, id: String) -> Result<(), String> {
+    delete_book(&app_handle, &id).await
+}
+
+// 从回收站恢复书籍
 #[tauri::command]
-async fn read_epub_file_content_command(file_path: String) -> Result<Vec<u8>, String> {
-    read_epub_file_content(&file_path).await
+async fn restore_book_command(app_handle: AppHandle, id: String) -> Result<EpubFile, String> {
+    restore_book(&app_handle, &id).await
+}
+
+// 获取回收站中的书籍
+#[tauri::command]
+async fn load_trashed_books_command(app_handle: AppHandle) -> Result<Vec<TrashedBook>, String> {
+    load_trashed_books(&app_handle).await
+}
+
+// 清空回收站，retention_days为空时全部删除
+#[tauri::command]
+async fn purge_trash_command(
+    app_handle: AppHandle,
+    retention_days: Option<u64>,
+) -> Result<Vec<String>, String> {
+    purge_trash(&app_handle, retention_days).await
+}
+
+// 查找重复书籍
+#[tauri::command]
+async fn find_duplicate_books_command(
+    app_handle: AppHandle,
+) -> Result<Vec<DuplicateGroup>, String> {
+    find_duplicate_books(&app_handle).await
+}
+
+// 合并重复书籍，保留一本并将其他书籍的书签移过来
+#[tauri::command]
+async fn merge_duplicate_books_command(
+    app_handle: AppHandle,
+    keep_id: String,
+    remove_ids: Vec<String>,
+) -> Result<EpubFile, String> {
+    merge_duplicate_books(&app_handle, &keep_id, remove_ids).await
+}
+
+// 使用图片文件作为自定义封面
+#[tauri::command]
+async fn set_custom_cover_from_file_command(
+    app_handle: AppHandle,
+    book_id: String,
+    image_path: String,
+) -> Result<EpubFile, String> {
+    set_custom_cover_from_file(&app_handle, &book_id, &image_path).await
+}
+
+// 列出epub中的图片，供选择封面
+#[tauri::command]
+async fn list_epub_images_command(
+    app_handle: AppHandle,
+    book_id: String,
+) -> Result<Vec<EpubImage>, String> {
+    list_epub_images(&app_handle, &book_id).await
+}
+
+// 使用epub中的图片作为自定义封面
+#[tauri::command]
+async fn set_custom_cover_from_epub_command(
+    app_handle: AppHandle,
+    book_id: String,
+    resource_id: String,
+) -> Result<EpubFile, String> {
+    set_custom_cover_from_epub(&app_handle, &book_id, &resource_id).await
+}
+
+// 恢复使用epub中提取的封面
+#[tauri::command]
+async fn revert_custom_cover_command(
+    app_handle: AppHandle,
+    book_id: String,
+) -> Result<EpubFile, String> {
+    revert_custom_cover(&app_handle, &book_id).await
+}
+
+// 获取所有书架
+#[tauri::command]
+async fn load_collections_command(app_handle: AppHandle) -> Result<Vec<Collection>, String> {
+    load_collections(&app_handle).await
+}
+
+// 新建书架
+#[tauri::command]
+async fn create_collection_command(
+    app_handle: AppHandle,
+    name: String,
+) -> Result<Collection, String> {
+    create_collection(&app_handle, &name).await
+}
+
+// 重命名书架
+#[tauri::command]
+async fn rename_collection_command(
+    app_handle: AppHandle,
+    collection_id: String,
+    name: String,
+) -> Result<Collection, String> {
+    rename_collection(&app_handle, &collection_id, &name).await
+}
+
+// 删除书架
+#[tauri::command]
+async fn delete_collection_command(
+    app_handle: AppHandle,
+    collection_id: String,
+) -> Result<(), String> {
+    delete_collection(&app_handle, &collection_id).await
+}
+
+// 将书籍加入书架，position为空时追加到末尾
+#[tauri::command]
+async fn add_book_to_collection_command(
+    app_handle: AppHandle,
+    collection_id: String,
+    book_id: String,
+    position: Option<usize>,
+) -> Result<Collection, String> {
+    add_book_to_collection(&app_handle, &collection_id, &book_id, position).await
+}
+
+// 将书籍移出书架
+#[tauri::command]
+async fn remove_book_from_collection_command(
+    app_handle: AppHandle,
+    collection_id: String,
+    book_id: String,
+) -> Result<Collection, String> {
+    remove_book_from_collection(&app_handle, &collection_id, &book_id).await
+}
+
+// 重新排列书架中的书籍
+#[tauri::command]
+async fn reorder_collection_command(
+    app_handle: AppHandle,
+    collection_id: String,
+    book_ids: Vec<String>,
+) -> Result<Collection, String> {
+    reorder_collection(&app_handle, &collection_id, book_ids).await
+}
+
+// 获取书架中的书籍
+#[tauri::command]
+async fn load_collection_books_command(
+    app_handle: AppHandle,
+    collection_id: String,
+) -> Result<Vec<EpubFile>, String> {
+    load_collection_books(&app_handle, &collection_id).await
+}
+
+// 获取所有智能书架
+#[tauri::command]
+async fn load_smart_shelves_command(app_handle: AppHandle) -> Result<Vec<SmartShelf>, String> {
+    load_smart_shelves(&app_handle).await
+}
+
+// 保存智能书架，id为空时新建
+#[tauri::command]
+async fn save_smart_shelf_command(
+    app_handle: AppHandle,
+    id: Option<String>,
+    name: String,
+    rule: SmartRule,
+) -> Result<SmartShelf, String> {
+    save_smart_shelf(&app_handle, id, &name, rule).await
+}
+
+// 删除智能书架
+#[tauri::command]
+async fn delete_smart_shelf_command(app_handle: AppHandle, id: String) -> Result<(), String> {
+    delete_smart_shelf(&app_handle, &id).await
+}
+
+// 获取智能书架中的书籍
+#[tauri::command]
+async fn evaluate_smart_shelf_command(
+    app_handle: AppHandle,
+    id: String,
+) -> Result<Vec<EpubFile>, String> {
+    evaluate_smart_shelf(&app_handle, &id).await
+}
+
+// 预览未保存的规则
+#[tauri::command]
+async fn evaluate_smart_rule_command(
+    app_handle: AppHandle,
+    rule: SmartRule,
+) -> Result<Vec<EpubFile>, String> {
+    evaluate_smart_rule(&app_handle, &rule).await
+}
+
+// 获取 TXT 导入配置
+#[tauri::command]
+async fn load_txt_import_settings_command(
+    app_handle: AppHandle,
+) -> Result<TxtImportSettings, String> {
+    load_txt_import_settings(&app_handle).await
+}
+
+// 保存 TXT 导入配置
+#[tauri::command]
+async fn save_txt_import_settings_command(
+    app_handle: AppHandle,
+    settings: TxtImportSettings,
+) -> Result<(), String> {
+    save_txt_import_settings(&app_handle, &settings).await
+}
+
+// 获取所有监视文件夹
+#[tauri::command]
+async fn load_watch_folders_command(app_handle: AppHandle) -> Result<Vec<String>, String> {
+    load_watch_folders(&app_handle).await
+}
+
+// 添加监视文件夹
+#[tauri::command]
+async fn add_watch_folder_command(
+    app_handle: AppHandle,
+    state: State<'_, WatchState>,
+    folder: String,
+) -> Result<Vec<String>, String> {
+    add_watch_folder(&app_handle, state, &folder).await
+}
+
+// 移除监视文件夹
+#[tauri::command]
+async fn remove_watch_folder_command(
+    app_handle: AppHandle,
+    state: State<'_, WatchState>,
+    folder: String,
+) -> Result<Vec<String>, String> {
+    remove_watch_folder(&app_handle, state, &folder).await
+}
+
+// 获取书籍内容的协议地址，阅读器通过协议按需读取epub中的资源
+#[tauri::command]
+async fn get_book_url_command(file_path: String) -> Result<String, String> {
+    book_url(&file_path)
 }
 
 // 保存阅读器样式
@@ -103,6 +482,102 @@ async fn get_bookmark_command(book_path: &str) -> Result<BookMark, String> {
     load_bookmark_from_local_storage(book_path).await
 }
 
+// 获取书籍的目录和阅读顺序
+#[tauri::command]
+async fn get_book_navigation_command(
+    app_handle: AppHandle,
+    id: String,
+) -> Result<BookNavigation, String> {
+    get_book_navigation(&app_handle, &id).await
+}
+
+// 提交书内搜索任务，结果通过 book-search-result 事件发送
+#[tauri::command]
+async fn start_book_search_command(
+    app_handle: AppHandle,
+    id: String,
+    query: BookSearchQuery,
+) -> Result<String, String> {
+    start_book_search(&app_handle, &id, query).await
+}
+
+// 在整个书库中全文搜索
+#[tauri::command]
+async fn search_fulltext_command(
+    app_handle: AppHandle,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<FullTextHit>, String> {
+    search_fulltext(&app_handle, &query, limit).await
+}
+
+// 提交重建全文索引的后台任务
+#[tauri::command]
+async fn start_fulltext_rebuild_job_command(app_handle: AppHandle) -> Result<String, String> {
+    start_fulltext_rebuild_job(&app_handle)
+}
+
+// 保存阅读进度
+#[tauri::command]
+async fn save_reading_progress_command(
+    app_handle: AppHandle,
+    id: String,
+    cfi: String,
+    percentage: f64,
+    chapter_href: Option<String>,
+) -> Result<ReadingProgress, String> {
+    save_reading_progress(&app_handle, &id, cfi, percentage, chapter_href).await
+}
+
+// 获取阅读进度
+#[tauri::command]
+async fn get_reading_progress_command(
+    app_handle: AppHandle,
+    id: String,
+) -> Result<Option<ReadingProgress>, String> {
+    get_reading_progress(&app_handle, &id).await
+}
+
+// 开始一次阅读记录
+#[tauri::command]
+async fn start_reading_session_command(
+    app_handle: AppHandle,
+    book_id: String,
+    cfi: String,
+) -> Result<ReadingSession, String> {
+    start_reading_session(&app_handle, &book_id, cfi).await
+}
+
+// 更新阅读记录，阅读过程中定时调用，关闭书籍时再调用一次
+#[tauri::command]
+async fn update_reading_session_command(
+    app_handle: AppHandle,
+    book_id: String,
+    session_id: String,
+    cfi: String,
+    pages_turned: u32,
+) -> Result<ReadingSession, String> {
+    update_reading_session(&app_handle, &book_id, &session_id, cfi, pages_turned).await
+}
+
+// 获取一本书的阅读记录
+#[tauri::command]
+async fn load_reading_sessions_command(
+    app_handle: AppHandle,
+    book_id: String,
+) -> Result<Vec<ReadingSession>, String> {
+    load_reading_sessions(&app_handle, &book_id).await
+}
+
+// 获取阅读时间统计
+#[tauri::command]
+async fn get_reading_stats_command(
+    app_handle: AppHandle,
+    query: ReadingStatsQuery,
+) -> Result<ReadingStats, String> {
+    get_reading_stats(&app_handle, query).await
+}
+
 // 更新最后打开时间
 #[tauri::command]
 async fn update_last_opened_command(file_path: String) -> Result<(), String> {
@@ -115,13 +590,22 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
+        .manage(WatchState::default())
+        .manage(JobState::default())
+        // 阅读器通过 rbook:// 协议按需读取epub中的资源
+        .register_asynchronous_uri_scheme_protocol(BOOK_PROTOCOL, handle_book_protocol)
         .setup(|app| {
-            // 将默认封面图片复制到应用程序目录
-            let resource_path = app
-                .path()
-                .resolve("resources/default_cover.png", BaseDirectory::Resource)?;
             let app_handle = app.handle();
-            init_default_cover(&app_handle, &resource_path.to_string_lossy())?;
+
+            // 在后台将旧版MD5命名的书籍目录迁移为SHA-256命名
+            if let Err(e) = start_migration_job(app_handle) {
+                println!("Failed to migrate book directories: {}", e);
+            }
+
+            // 监视文件夹，自动导入新的epub文件
+            if let Err(e) = init_watch_folders(app_handle) {
+                println!("Failed to start watching folders: {}", e);
+            }
 
             // setup the tray icon
             setup_tray(app).unwrap();
@@ -130,12 +614,60 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             save_file_and_return_local_path_command,
+            import_epub_directory_command,
+            start_import_job_command,
+            start_reindex_job_command,
+            start_export_job_command,
+            start_restore_job_command,
+            start_integrity_check_job_command,
+            cancel_job_command,
+            get_job_command,
+            list_jobs_command,
             load_all_local_epub_files_command,
-            read_epub_file_content_command,
+            query_library_command,
+            delete_book_command,
+            restore_book_command,
+            load_trashed_books_command,
+            purge_trash_command,
+            find_duplicate_books_command,
+            merge_duplicate_books_command,
+            set_custom_cover_from_file_command,
+            list_epub_images_command,
+            set_custom_cover_from_epub_command,
+            revert_custom_cover_command,
+            load_collections_command,
+            create_collection_command,
+            rename_collection_command,
+            delete_collection_command,
+            add_book_to_collection_command,
+            remove_book_from_collection_command,
+            reorder_collection_command,
+            load_collection_books_command,
+            load_smart_shelves_command,
+            save_smart_shelf_command,
+            delete_smart_shelf_command,
+            evaluate_smart_shelf_command,
+            evaluate_smart_rule_command,
+            load_txt_import_settings_command,
+            save_txt_import_settings_command,
+            load_watch_folders_command,
+            add_watch_folder_command,
+            remove_watch_folder_command,
+            get_book_url_command,
             save_reader_style_command,
             get_reader_style_command,
             save_bookmark_command,
             get_bookmark_command,
+            get_book_navigation_command,
+            start_book_search_command,
+            search_fulltext_command,
+            start_fulltext_rebuild_job_command,
+            save_reading_progress_command,
+            get_reading_progress_command,
+            start_reading_session_command,
+            update_reading_session_command,
+            load_reading_sessions_command,
+            get_reading_stats_command,
             update_last_opened_command,
         ])
         .run(tauri::generate_context!())
@@ -0,0 +1,439 @@
+use crate::cover::CoverSize;
+use crate::cover::{find_cover_file, fix_cover_extension, COVER_VERSION, CUSTOM_COVER_DIR};
+use crate::file::{
+    find_epub_in_dir, get_current_timestamp, get_last_opened, is_book_importing, read_epub_cover,
+};
+use crate::jobs::{spawn_job, JobHandle};
+use crate::metadata::{load_or_extract_metadata, read_epub_metadata, save_metadata};
+use crate::model::{
+    EpubFile, JobKind, LibraryIndex, LibraryPage, LibraryQuery, LibraryRecord, LibrarySortKey,
+    SortDirection,
+};
+use crate::progress::get_progress_percentage;
+use crate::protocol::cover_url;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use tauri::AppHandle;
+use tauri::Manager;
+
+// 书库索引的读写锁，避免并发导入时互相覆盖
+static LIBRARY_LOCK: Mutex<()> = Mutex::new(());
+
+// 获取书籍存放目录 /com.rbook.app/books
+pub fn get_books_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+    Ok(app_dir.join("books"))
+}
+
+// 获取书籍目录 /com.rbook.app/books/<id>，校验ID防止路径穿越
+pub fn get_book_dir(app_handle: &AppHandle, id: &str) -> Result<PathBuf, String> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid book id: {}", id));
+    }
+    Ok(get_books_dir(app_handle)?.join(id))
+}
+
+// 书库索引文件路径 /com.rbook.app/config/library.json
+fn get_library_index_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+    Ok(app_dir.join("config").join("library.json"))
+}
+
+// 读取书库索引，文件不存在时返回空索引
+fn read_library_index(index_path: &Path) -> Result<LibraryIndex, String> {
+    if !index_path.exists() {
+        return Ok(LibraryIndex::default());
+    }
+    let json_data = fs::read_to_string(index_path)
+        .map_err(|e| format!("Failed to read library index: {}", e))?;
+    match serde_json::from_str(&json_data) {
+        Ok(index) => Ok(index),
+        Err(e) => {
+            // 索引损坏时重新扫描生成
+            println!("Library index is corrupt, rebuilding: {}", e);
+            Ok(LibraryIndex::default())
+        }
+    }
+}
+
+// 写入书库索引
+fn write_library_index(index_path: &Path, index: &LibraryIndex) -> Result<(), String> {
+    if let Some(config_dir) = index_path.parent() {
+        if !config_dir.exists() {
+            fs::create_dir_all(config_dir)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+    }
+    let json_data = serde_json::to_string(index)
+        .map_err(|e| format!("Failed to serialize library index: {}", e))?;
+    fs::write(index_path, json_data).map_err(|e| format!("Failed to write library index: {}", e))
+}
+
+// 获取目录的创建时间，用于没有索引记录的旧书籍
+fn get_dir_created_time(dir: &Path) -> Option<u64> {
+    let meta = fs::metadata(dir).ok()?;
+    let time = meta.created().or_else(|_| meta.modified()).ok()?;
+    time.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+}
+
+// 根据书籍目录生成索引记录，封面不存在时从epub中提取
+pub fn build_library_record(hash_dir: &Path, epub_path: &Path) -> Result<LibraryRecord, String> {
+    let id = hash_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or("Failed to get book id")?;
+    let file_name = epub_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or("Failed to get file name")?;
+    let file_size = fs::metadata(epub_path)
+        .map_err(|e| format!("Failed to read file size: {}", e))?
+        .len();
+
+    // 缩略图在第一次请求时生成
+    let cover_file = match find_cover_file(hash_dir) {
+        Some(cover_file) => fix_cover_extension(hash_dir, &cover_file),
+        None => read_epub_cover(hash_dir, epub_path)?,
+    };
+
+    let now = get_current_timestamp()?;
+    Ok(LibraryRecord {
+        id,
+        file_name,
+        cover_file,
+        cover_version: COVER_VERSION,
+        custom_cover_file: find_cover_file(&hash_dir.join(CUSTOM_COVER_DIR)),
+        metadata: load_or_extract_metadata(hash_dir, epub_path),
+        added_at: get_dir_created_time(hash_dir).unwrap_or(now),
+        updated_at: now,
+        file_size,
+    })
+}
+
+// 将索引记录转换为返回给前端的EpubFile
+pub fn epub_file_from_record(books_dir: &Path, record: &LibraryRecord) -> EpubFile {
+    let hash_dir = books_dir.join(&record.id);
+    // 用户设置了自定义封面时优先使用
+    let (cover_dir, cover_file) = match &record.custom_cover_file {
+        Some(custom_cover_file) => (hash_dir.join(CUSTOM_COVER_DIR), custom_cover_file),
+        None => (hash_dir.clone(), &record.cover_file),
+    };
+    EpubFile {
+        id: record.id.clone(),
+        cover: cover_dir.join(cover_file).to_str().unwrap().to_string(),
+        path: hash_dir
+            .join(&record.file_name)
+            .to_str()
+            .unwrap()
+            .to_string(),
+        last_opened: get_last_opened(&hash_dir),
+        progress: get_progress_percentage(&hash_dir),
+        thumbnail: cover_url(&record.id, CoverSize::Grid),
+        thumbnail_small: cover_url(&record.id, CoverSize::List),
+        has_custom_cover: record.custom_cover_file.is_some(),
+        metadata: record.metadata.clone(),
+        added_at: record.added_at,
+        file_size: record.file_size,
+    }
+}
+
+// 新增或更新一条索引记录（导入时调用）
+pub fn upsert_library_record(
+    app_handle: &AppHandle,
+    mut record: LibraryRecord,
+) -> Result<(), String> {
+    let _guard = LIBRARY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let index_path = get_library_index_path(app_handle)?;
+    let mut index = read_library_index(&index_path)?;
+    match index.books.iter_mut().find(|r| r.id == record.id) {
+        Some(existing) => {
+            // 保留最初的导入时间
+            record.added_at = existing.added_at;
+            *existing = record;
+        }
+        None => index.books.push(record),
+    }
+    write_library_index(&index_path, &index)
+}
+
+// 从索引中查找一条书籍记录
+pub fn get_library_record(
+    app_handle: &AppHandle,
+    id: &str,
+) -> Result<Option<LibraryRecord>, String> {
+    let _guard = LIBRARY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let index = read_library_index(&get_library_index_path(app_handle)?)?;
+    Ok(index.books.into_iter().find(|r| r.id == id))
+}
+
+// 删除一条索引记录（删除书籍时调用）
+pub fn remove_library_record(app_handle: &AppHandle, id: &str) -> Result<(), String> {
+    let _guard = LIBRARY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let index_path = get_library_index_path(app_handle)?;
+    let mut index = read_library_index(&index_path)?;
+    let count = index.books.len();
+    index.books.retain(|r| r.id != id);
+    if index.books.len() != count {
+        write_library_index(&index_path, &index)?;
+    }
+    Ok(())
+}
+
+// 对比索引与books目录，补充应用外新增的书籍目录，移除已不存在的书籍
+// 只在发现差异时才打开epub文件，正常启动时只需列出目录
+// 锁内只对比目录，打开epub和提取封面在锁外的阻塞线程中执行，避免一本书拖慢所有书库命令
+pub async fn reconcile_library_index(app_handle: &AppHandle) -> Result<LibraryIndex, String> {
+    let books_dir = get_books_dir(app_handle)?;
+    let index_path = get_library_index_path(app_handle)?;
+
+    let missing = {
+        let _guard = LIBRARY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut index = read_library_index(&index_path)?;
+        if !books_dir.exists() {
+            return Ok(index);
+        }
+
+        // 移除目录或epub文件已不存在的记录
+        let count = index.books.len();
+        index
+            .books
+            .retain(|r| books_dir.join(&r.id).join(&r.file_name).exists());
+        let mut changed = index.books.len() != count;
+
+        // 旧版本的封面可能以错误的扩展名保存
+        for record in index
+            .books
+            .iter_mut()
+            .filter(|r| r.cover_version < COVER_VERSION)
+        {
+            record.cover_file =
+                fix_cover_extension(&books_dir.join(&record.id), &record.cover_file);
+            record.cover_version = COVER_VERSION;
+            changed = true;
+        }
+        if changed {
+            write_library_index(&index_path, &index)?;
+        }
+
+        // 索引中没有的书籍目录
+        let known: HashSet<String> = index.books.iter().map(|r| r.id.clone()).collect();
+        let mut missing = Vec::new();
+        for hash_dir_entry in fs::read_dir(&books_dir)
+            .map_err(|e| format!("Failed to read books directory: {}", e))?
+        {
+            let hash_dir_entry =
+                hash_dir_entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+            let hash_dir = hash_dir_entry.path();
+            let id = hash_dir_entry.file_name().to_string_lossy().to_string();
+            // 正在导入的书籍由导入流程写入索引，epub文件可能还没有复制完
+            if !hash_dir.is_dir() || known.contains(&id) || is_book_importing(&id) {
+                continue;
+            }
+            if let Some(epub_path) = find_epub_in_dir(&hash_dir) {
+                missing.push((hash_dir, epub_path));
+            }
+        }
+
+        if missing.is_empty() {
+            return Ok(index);
+        }
+        missing
+    };
+
+    let added: Vec<LibraryRecord> = tokio::task::spawn_blocking(move || {
+        missing
+            .into_iter()
+            .filter_map(
+                |(hash_dir, epub_path)| match build_library_record(&hash_dir, &epub_path) {
+                    Ok(record) => Some(record),
+                    Err(e) => {
+                        println!("Failed to index {}: {}", hash_dir.display(), e);
+                        None
+                    }
+                },
+            )
+            .collect()
+    })
+    .await
+    .map_err(|e| format!("Reconcile task failed: {}", e))?;
+
+    // 重新读取索引再写入，期间其他命令可能已修改索引
+    let _guard = LIBRARY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut index = read_library_index(&index_path)?;
+    let mut changed = false;
+    for record in added {
+        if !index.books.iter().any(|r| r.id == record.id)
+            && books_dir.join(&record.id).join(&record.file_name).exists()
+        {
+            index.books.push(record);
+            changed = true;
+        }
+    }
+    if changed {
+        write_library_index(&index_path, &index)?;
+    }
+    Ok(index)
+}
+
+// 书籍的显示标题，没有标题时使用文件名
+fn display_title(record: &LibraryRecord) -> String {
+    record
+        .metadata
+        .title
+        .clone()
+        .unwrap_or_else(|| record.file_name.clone())
+        .to_lowercase()
+}
+
+// 书籍的第一作者
+fn first_author(record: &LibraryRecord) -> Option<String> {
+    record
+        .metadata
+        .creators
+        .first()
+        .map(|c| c.name.to_lowercase())
+}
+
+// 判断书籍是否匹配过滤文本
+fn matches_filter(record: &LibraryRecord, filter: &str) -> bool {
+    display_title(record).contains(filter)
+        || record.file_name.to_lowercase().contains(filter)
+        || record
+            .metadata
+            .creators
+            .iter()
+            .any(|c| c.name.to_lowercase().contains(filter))
+}
+
+// 比较可选值，缺失的值总是排在最后
+fn compare_optional<T: Ord>(a: Option<T>, b: Option<T>, direction: SortDirection) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => match direction {
+            SortDirection::Asc => a.cmp(&b),
+            SortDirection::Desc => b.cmp(&a),
+        },
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+// 分页查询书库，支持排序和文本过滤
+pub async fn query_library(
+    app_handle: &AppHandle,
+    query: &LibraryQuery,
+) -> Result<LibraryPage, String> {
+    let books_dir = get_books_dir(app_handle)?;
+    let index = reconcile_library_index(app_handle).await?;
+
+    let filter = query
+        .filter
+        .as_deref()
+        .map(|f| f.trim().to_lowercase())
+        .filter(|f| !f.is_empty());
+    let mut records: Vec<(&LibraryRecord, Option<u64>)> = index
+        .books
+        .iter()
+        .filter(|record| filter.as_deref().is_none_or(|f| matches_filter(record, f)))
+        .map(|record| (record, get_last_opened(&books_dir.join(&record.id))))
+        .collect();
+
+    records.sort_by(|(a, a_opened), (b, b_opened)| {
+        let ordering = match query.sort_by {
+            LibrarySortKey::Title => compare_optional(
+                Some(display_title(a)),
+                Some(display_title(b)),
+                query.direction,
+            ),
+            LibrarySortKey::Author => {
+                compare_optional(first_author(a), first_author(b), query.direction)
+            }
+            LibrarySortKey::AddedAt => {
+                compare_optional(Some(a.added_at), Some(b.added_at), query.direction)
+            }
+            LibrarySortKey::LastOpened => compare_optional(*a_opened, *b_opened, query.direction),
+            LibrarySortKey::FileSize => {
+                compare_optional(Some(a.file_size), Some(b.file_size), query.direction)
+            }
+        };
+        // 排序值相同时按标题排序，保证分页结果稳定
+        ordering.then_with(|| display_title(a).cmp(&display_title(b)))
+    });
+
+    let total = records.len();
+    let limit = query.limit.unwrap_or(total);
+    let books = records
+        .into_iter()
+        .skip(query.offset)
+        .take(limit)
+        .map(|(record, _)| epub_file_from_record(&books_dir, record))
+        .collect();
+
+    Ok(LibraryPage { total, books })
+}
+
+// 重新扫描所有书籍，重新解析元数据并重建索引记录
+async fn reindex_library(app_handle: &AppHandle, job: JobHandle) -> Result<usize, String> {
+    let books_dir = get_books_dir(app_handle)?;
+    if !books_dir.exists() {
+        return Ok(0);
+    }
+    let hash_dirs: Vec<PathBuf> = fs::read_dir(&books_dir)
+        .map_err(|e| format!("Failed to read books directory: {}", e))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    job.set_total(hash_dirs.len());
+
+    let mut indexed = 0;
+    for hash_dir in hash_dirs {
+        if job.is_cancelled() {
+            break;
+        }
+        let id = hash_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string());
+        if let Some(epub_path) = find_epub_in_dir(&hash_dir) {
+            let dir = hash_dir.clone();
+            let record = tokio::task::spawn_blocking(move || {
+                let metadata = read_epub_metadata(&epub_path)?;
+                save_metadata(&dir, &metadata)?;
+                build_library_record(&dir, &epub_path)
+            })
+            .await
+            .map_err(|e| format!("Reindex task failed: {}", e))?;
+            match record {
+                Ok(record) => {
+                    upsert_library_record(app_handle, record)?;
+                    indexed += 1;
+                }
+                Err(e) => println!("Failed to reindex {}: {}", hash_dir.display(), e),
+            }
+        }
+        job.advance(id);
+    }
+
+    // 清理已不存在的记录
+    reconcile_library_index(app_handle).await?;
+    Ok(indexed)
+}
+
+// 提交后台重建索引任务，返回任务ID
+pub fn start_reindex_job(app_handle: &AppHandle) -> Result<String, String> {
+    let handle = app_handle.clone();
+    spawn_job(app_handle, JobKind::Reindex, move |job| async move {
+        let indexed = reindex_library(&handle, job).await?;
+        Ok(serde_json::json!({ "indexed": indexed }))
+    })
+}
@@ -0,0 +1,493 @@
+use crate::epub_builder::{
+    chapter_file_name, guess_language, import_generated_book, GeneratedBook, GeneratedChapter,
+    GeneratedResource,
+};
+use crate::model::{EpubFile, TocItem};
+use crate::util::percent_decode;
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+// 判断文件是否为 Markdown 文件
+pub fn is_markdown_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"))
+        .unwrap_or(false)
+}
+
+fn markdown_options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+}
+
+// 文档中的一个顶层标题
+struct Heading {
+    level: usize,
+    label: String,
+    id: String,
+}
+
+// 一个 Markdown 文档
+struct MarkdownDoc {
+    path: PathBuf, // Canonical path, used to resolve links between documents
+    source: String,
+    headings: Vec<Heading>,
+    preamble: bool, // Whether there is content before the first heading
+}
+
+// 转换过程中共享的状态：标题所在的章节，以及已收集的图片
+#[derive(Default)]
+struct ConvertContext {
+    doc_chapters: HashMap<PathBuf, usize>,
+    anchors: HashMap<PathBuf, HashMap<String, usize>>,
+    images: HashMap<PathBuf, String>,
+    resources: Vec<GeneratedResource>,
+}
+
+// 按 GitHub 的规则由标题生成锚点，重复的锚点依次加上 -1、-2
+fn slugify(label: &str, used: &mut HashMap<String, usize>) -> String {
+    let mut slug: String = label
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect();
+    if slug.is_empty() {
+        slug = "section".to_string();
+    }
+    let count = used.entry(slug.clone()).or_default();
+    *count += 1;
+    if *count > 1 {
+        slug = format!("{}-{}", slug, *count - 1);
+    }
+    slug
+}
+
+// 读取 Markdown 文件并收集顶层标题（引用块和列表中的标题不计入目录）
+fn read_markdown_doc(path: &Path) -> Result<MarkdownDoc, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let source = String::from_utf8_lossy(&data)
+        .trim_start_matches('\u{feff}')
+        .to_string();
+
+    let mut headings = Vec::new();
+    let mut used = HashMap::new();
+    let mut preamble = false;
+    let mut depth = 0usize;
+    let mut current: Option<(usize, String)> = None;
+    for event in Parser::new_ext(&source, markdown_options()) {
+        match event {
+            Event::Start(Tag::Heading(level, _, _)) if depth == 0 => {
+                current = Some((level as usize, String::new()));
+                depth += 1;
+            }
+            Event::Start(_) => {
+                preamble |= depth == 0 && headings.is_empty();
+                depth += 1;
+            }
+            Event::End(Tag::Heading(..)) if depth == 1 => {
+                if let Some((level, label)) = current.take() {
+                    let id = slugify(&label, &mut used);
+                    headings.push(Heading { level, label, id });
+                }
+                depth -= 1;
+            }
+            Event::End(_) => depth = depth.saturating_sub(1),
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, label)) = current.as_mut() {
+                    label.push_str(&text);
+                }
+            }
+            _ => preamble |= depth == 0 && headings.is_empty(),
+        }
+    }
+
+    Ok(MarkdownDoc {
+        path: path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+        source,
+        headings,
+        preamble,
+    })
+}
+
+// 判断链接是否指向外部（带协议或绝对路径）
+fn is_external(url: &str) -> bool {
+    url.starts_with('/')
+        || url
+            .split_once(':')
+            .is_some_and(|(scheme, _)| scheme.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+impl ConvertContext {
+    // 将指向其他 Markdown 文档或标题的链接改为指向生成的章节
+    fn rewrite_link(&self, doc_path: &Path, url: &str) -> Option<String> {
+        if is_external(url) {
+            return None;
+        }
+        let (path, fragment) = url.split_once('#').unwrap_or((url, ""));
+        let target = if path.is_empty() {
+            doc_path.to_path_buf()
+        } else {
+            doc_path
+                .parent()?
+                .join(percent_decode(path)?)
+                .canonicalize()
+                .ok()?
+        };
+        let chapter = self
+            .anchors
+            .get(&target)
+            .and_then(|anchors| anchors.get(fragment))
+            .or_else(|| self.doc_chapters.get(&target))?;
+        Some(if fragment.is_empty() {
+            chapter_file_name(*chapter)
+        } else {
+            format!("{}#{}", chapter_file_name(*chapter), fragment)
+        })
+    }
+
+    // 将本地图片加入 epub 资源，返回资源路径
+    fn embed_image(&mut self, doc_path: &Path, url: &str) -> Option<String> {
+        if is_external(url) {
+            return None;
+        }
+        let path = doc_path
+            .parent()?
+            .join(percent_decode(url.split(['?', '#']).next()?)?)
+            .canonicalize()
+            .ok()?;
+        if let Some(name) = self.images.get(&path) {
+            return Some(name.clone());
+        }
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        let mime = match ext.as_str() {
+            "jpg" | "jpeg" => "image/jpeg",
+            "png" => "image/png",
+            "gif" => "image/gif",
+            "webp" => "image/webp",
+            "svg" => "image/svg+xml",
+            _ => return None,
+        };
+        let data = fs::read(&path).ok()?;
+        let name = format!("images/image_{:04}.{}", self.resources.len() + 1, ext);
+        self.resources.push(GeneratedResource {
+            name: name.clone(),
+            mime: mime.to_string(),
+            data,
+        });
+        self.images.insert(path, name.clone());
+        Some(name)
+    }
+
+    // 将文档渲染为 XHTML 片段，按顶层标题切分
+    // 返回的第0段为第一个标题之前的内容，第k段以第k个标题开头
+    fn render_segments(&mut self, doc: &MarkdownDoc) -> Vec<String> {
+        let mut segments = Vec::with_capacity(doc.headings.len() + 1);
+        let mut events: Vec<Event> = Vec::new();
+        let mut depth = 0usize;
+        let mut heading_index = 0;
+        for event in Parser::new_ext(&doc.source, markdown_options()) {
+            let event = match event {
+                Event::Start(Tag::Heading(level, _, classes)) if depth == 0 => {
+                    let mut html = String::new();
+                    html::push_html(&mut html, events.drain(..));
+                    segments.push(html);
+                    let id = doc.headings.get(heading_index).map(|h| h.id.as_str());
+                    heading_index += 1;
+                    Event::Start(Tag::Heading(level, id, classes))
+                }
+                Event::Start(Tag::Link(link_type, url, title)) => {
+                    let url = self
+                        .rewrite_link(&doc.path, &url)
+                        .map(CowStr::from)
+                        .unwrap_or(url);
+                    Event::Start(Tag::Link(link_type, url, title))
+                }
+                Event::Start(Tag::Image(link_type, url, title)) => {
+                    let url = self
+                        .embed_image(&doc.path, &url)
+                        .map(CowStr::from)
+                        .unwrap_or(url);
+                    Event::Start(Tag::Image(link_type, url, title))
+                }
+                // 原始 HTML 不一定是合法的 XHTML，会导致整章无法显示，按文本显示
+                Event::Html(text) => Event::Text(text),
+                event => event,
+            };
+            match &event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            events.push(event);
+        }
+        let mut html = String::new();
+        html::push_html(&mut html, events.into_iter());
+        segments.push(html);
+        segments
+    }
+}
+
+// 生成的 TOC 条目
+fn toc_item(label: &str, level: usize, chapter: usize, anchor: Option<&str>) -> TocItem {
+    let file = chapter_file_name(chapter);
+    TocItem {
+        label: label.trim().to_string(),
+        href: match anchor {
+            Some(anchor) => format!("{}#{}", file, anchor),
+            None => file,
+        },
+        level,
+        spine_index: Some(chapter),
+        subitems: Vec::new(),
+    }
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .or_else(|| path.file_name())
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Untitled".to_string())
+}
+
+// 将单个 Markdown 文件转换为书籍
+// 出现两次以上的最高一级标题作为章节，更低级的标题作为章节内的目录
+fn convert_markdown_file(path: &Path) -> Result<GeneratedBook, String> {
+    let doc = read_markdown_doc(path)?;
+    let min_level = doc.headings.iter().map(|h| h.level).min().unwrap_or(1);
+    let split_level =
+        (1..=6).find(|level| doc.headings.iter().filter(|h| h.level == *level).count() >= 2);
+    let title = match doc.headings.iter().filter(|h| h.level == min_level).count() {
+        1 => doc
+            .headings
+            .iter()
+            .find(|h| h.level == min_level)
+            .unwrap()
+            .label
+            .clone(),
+        _ => file_stem(path),
+    };
+    let base_level = split_level.unwrap_or(min_level);
+
+    // 计算每个片段所属的章节
+    let mut chapter_titles = vec![title.clone()];
+    let mut segment_chapters = vec![0];
+    let mut toc = Vec::new();
+    if doc.preamble || doc.headings.is_empty() {
+        toc.push(toc_item(&title, 0, 0, None));
+    }
+    for (index, heading) in doc.headings.iter().enumerate() {
+        let starts_chapter = split_level.is_some_and(|split| heading.level <= split);
+        let level = heading.level.max(base_level) - base_level;
+        if starts_chapter && (index > 0 || doc.preamble) {
+            chapter_titles.push(heading.label.clone());
+        } else if starts_chapter {
+            chapter_titles[0] = heading.label.clone();
+        }
+        let chapter = chapter_titles.len() - 1;
+        segment_chapters.push(chapter);
+        let anchor = (!starts_chapter).then_some(heading.id.as_str());
+        toc.push(toc_item(&heading.label, level, chapter, anchor));
+    }
+
+    let mut context = ConvertContext::default();
+    context.doc_chapters.insert(doc.path.clone(), 0);
+    context.anchors.insert(
+        doc.path.clone(),
+        doc.headings
+            .iter()
+            .zip(&segment_chapters[1..])
+            .map(|(heading, chapter)| (heading.id.clone(), *chapter))
+            .collect(),
+    );
+
+    let mut chapters: Vec<GeneratedChapter> = chapter_titles
+        .into_iter()
+        .map(|title| GeneratedChapter {
+            title,
+            body: String::new(),
+        })
+        .collect();
+    for (segment, chapter) in context
+        .render_segments(&doc)
+        .into_iter()
+        .zip(segment_chapters)
+    {
+        chapters[chapter].body.push_str(&segment);
+    }
+
+    Ok(GeneratedBook {
+        language: guess_language(&doc.source).to_string(),
+        title,
+        author: None,
+        chapters,
+        toc,
+        resources: context.resources,
+    })
+}
+
+// 递归收集目录下的 Markdown 文件
+fn collect_markdown_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    for entry in fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read directory {}: {}", dir.display(), e))?
+    {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        let path = entry.path();
+        if path.is_dir() {
+            collect_markdown_files(&path, files)?;
+        } else if is_markdown_file(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+// 文件夹中包含的文件类型
+#[derive(Default)]
+struct DirContents {
+    markdown: bool,
+    epub: bool,
+}
+
+// 递归检查文件夹中的文件类型，找到 epub 文件后立即停止
+// 不跟随符号链接的目录，避免形成循环
+fn inspect_dir(dir: &Path, contents: &mut DirContents) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if contents.epub {
+            return;
+        }
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            inspect_dir(&path, contents);
+        } else if is_markdown_file(&path) {
+            contents.markdown = true;
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("epub"))
+        {
+            contents.epub = true;
+        }
+    }
+}
+
+// 文件夹中是否有 Markdown 文件，包含 epub 文件的文件夹按书库文件夹导入，不转换
+pub fn is_markdown_dir(dir: &Path) -> bool {
+    let mut contents = DirContents::default();
+    inspect_dir(dir, &mut contents);
+    contents.markdown && !contents.epub
+}
+
+// 将 Markdown 文件夹转换为书籍，每个文件为一章
+// 按路径排序，README 和 index 排在所在目录的最前面
+fn convert_markdown_dir(dir: &Path) -> Result<GeneratedBook, String> {
+    let mut files = Vec::new();
+    collect_markdown_files(dir, &mut files)?;
+    if files.is_empty() {
+        return Err(format!("No markdown files found in {}", dir.display()));
+    }
+    files.sort_by_key(|path| {
+        let is_index = matches!(file_stem(path).to_lowercase().as_str(), "readme" | "index");
+        (
+            path.parent().map(Path::to_path_buf),
+            !is_index,
+            path.clone(),
+        )
+    });
+
+    let docs = files
+        .iter()
+        .map(|path| read_markdown_doc(path))
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut context = ConvertContext::default();
+    let mut toc = Vec::new();
+    let mut titles = Vec::new();
+    for (chapter, doc) in docs.iter().enumerate() {
+        context.doc_chapters.insert(doc.path.clone(), chapter);
+        context.anchors.insert(
+            doc.path.clone(),
+            doc.headings
+                .iter()
+                .map(|heading| (heading.id.clone(), chapter))
+                .collect(),
+        );
+
+        // 文件开头的标题作为章节名，其余标题作为章节内的目录
+        let title_heading = (!doc.preamble).then(|| doc.headings.first()).flatten();
+        let title = title_heading
+            .map(|heading| heading.label.clone())
+            .unwrap_or_else(|| file_stem(&doc.path));
+        toc.push(toc_item(&title, 0, chapter, None));
+        let rest = &doc.headings[title_heading.map_or(0, |_| 1)..];
+        let min_level = rest.iter().map(|h| h.level).min().unwrap_or(1);
+        for heading in rest {
+            let level = heading.level - min_level + 1;
+            toc.push(toc_item(&heading.label, level, chapter, Some(&heading.id)));
+        }
+        titles.push(title);
+    }
+
+    let mut chapters = Vec::with_capacity(docs.len());
+    for (doc, title) in docs.iter().zip(titles) {
+        chapters.push(GeneratedChapter {
+            title,
+            body: context.render_segments(doc).concat(),
+        });
+    }
+    let sample: String = docs.iter().map(|doc| doc.source.as_str()).collect();
+
+    Ok(GeneratedBook {
+        title: file_stem(dir),
+        author: None,
+        language: guess_language(&sample).to_string(),
+        chapters,
+        toc,
+        resources: context.resources,
+    })
+}
+
+// 将 Markdown 文件或文件夹转换为 epub 并导入书库
+// 返回epub文件信息以及是否为新导入
+pub async fn import_markdown(
+    app_handle: &AppHandle,
+    origin_path: &Path,
+) -> Result<(EpubFile, bool), String> {
+    let path = origin_path.to_path_buf();
+    let book = tokio::task::spawn_blocking(move || {
+        if path.is_dir() {
+            convert_markdown_dir(&path)
+        } else {
+            convert_markdown_file(&path)
+        }
+    })
+    .await
+    .map_err(|e| format!("Convert task failed: {}", e))??;
+    import_generated_book(app_handle, book, &file_stem(origin_path)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_dir_needs_markdown_and_no_epub() {
+        let dir = std::env::temp_dir().join(format!("rbook-markdown-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("notes")).unwrap();
+        assert!(!is_markdown_dir(&dir));
+
+        fs::write(dir.join("notes").join("README.MD"), "# Notes").unwrap();
+        assert!(is_markdown_dir(&dir));
+
+        fs::write(dir.join("book.epub"), "").unwrap();
+        assert!(!is_markdown_dir(&dir));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,315 @@
+use crate::model::{BookMetadata, Creator, Identifier};
+use epub::doc::EpubDoc;
+use std::collections::HashMap;
+use std::path::Path;
+use xml::reader::{EventReader, XmlEvent};
+
+// 正在解析中的OPF元素
+enum OpfElement {
+    Creator {
+        id: Option<String>,
+        role: Option<String>,
+    },
+    Identifier {
+        scheme: Option<String>,
+    },
+    Role {
+        refines: String,
+    },
+    Other,
+}
+
+// 从OPF中解析出的作者和标识符，EpubDoc::mdata 不保留属性，需要自行解析
+#[derive(Default)]
+struct OpfDetails {
+    creators: Vec<(Option<String>, Creator)>,
+    identifiers: Vec<Identifier>,
+    roles: HashMap<String, String>, // EPUB3 中通过 <meta refines="#id" property="role"> 指定的角色
+}
+
+// 解析OPF文件中的作者（含角色）和标识符
+fn parse_opf_details(opf: &str) -> OpfDetails {
+    let mut details = OpfDetails::default();
+    let mut current = OpfElement::Other;
+    let mut text = String::new();
+
+    for event in EventReader::new(opf.as_bytes()) {
+        match event {
+            Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) => {
+                let attr = |key: &str| {
+                    attributes
+                        .iter()
+                        .find(|a| a.name.local_name == key)
+                        .map(|a| a.value.trim().to_string())
+                        .filter(|v| !v.is_empty())
+                };
+                text.clear();
+                current = match name.local_name.as_str() {
+                    "creator" => OpfElement::Creator {
+                        id: attr("id"),
+                        role: attr("role"),
+                    },
+                    "identifier" => OpfElement::Identifier {
+                        scheme: attr("scheme"),
+                    },
+                    "meta" if attr("property").as_deref() == Some("role") => {
+                        match attr("refines") {
+                            Some(refines) => OpfElement::Role {
+                                refines: refines.trim_start_matches('#').to_string(),
+                            },
+                            None => OpfElement::Other,
+                        }
+                    }
+                    _ => OpfElement::Other,
+                };
+            }
+            Ok(XmlEvent::Characters(s)) | Ok(XmlEvent::CData(s)) => text.push_str(&s),
+            Ok(XmlEvent::EndElement { .. }) => {
+                let value = text.trim().to_string();
+                match std::mem::replace(&mut current, OpfElement::Other) {
+                    OpfElement::Creator { id, role } if !value.is_empty() => {
+                        details.creators.push((id, Creator { name: value, role }));
+                    }
+                    OpfElement::Identifier { scheme } if !value.is_empty() => {
+                        details
+                            .identifiers
+                            .push(classify_identifier(scheme, &value));
+                    }
+                    OpfElement::Role { refines } if !value.is_empty() => {
+                        details.roles.insert(refines, value);
+                    }
+                    _ => {}
+                }
+                text.clear();
+            }
+            Err(_) => break,
+            _ => {}
+        }
+    }
+
+    details
+}
+
+// 为没有 opf:role 属性的作者补上 refines 指定的角色
+fn creators_with_roles(details: &OpfDetails) -> Vec<Creator> {
+    details
+        .creators
+        .iter()
+        .map(|(id, creator)| Creator {
+            name: creator.name.clone(),
+            role: creator
+                .role
+                .clone()
+                .or_else(|| id.as_ref().and_then(|id| details.roles.get(id).cloned())),
+        })
+        .collect()
+}
+
+// 识别标识符类型（ISBN/UUID等），并去掉 urn: 前缀
+fn classify_identifier(scheme: Option<String>, value: &str) -> Identifier {
+    let lower = value.to_lowercase();
+    for prefix in ["isbn", "uuid"] {
+        let urn = format!("urn:{}:", prefix);
+        if lower.starts_with(&urn) {
+            return Identifier {
+                scheme: Some(prefix.to_string()),
+                value: value[urn.len()..].trim().to_string(),
+            };
+        }
+    }
+
+    let scheme = scheme.map(|s| s.to_lowercase()).or_else(|| {
+        let digits: String = value
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect();
+        let looks_like_isbn = (digits.len() == 10 || digits.len() == 13)
+            && value
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '-' || c == 'X' || c == 'x');
+        looks_like_isbn.then(|| "isbn".to_string())
+    });
+
+    Identifier {
+        scheme,
+        value: value.to_string(),
+    }
+}
+
+// 读取epub文件的OPF元数据
+pub fn read_epub_metadata(epub_path: &Path) -> Result<BookMetadata, String> {
+    let mut doc = EpubDoc::new(epub_path).map_err(|e| e.to_string())?;
+
+    let root_file = doc.root_file.clone();
+    let details = doc
+        .get_resource_str_by_path(&root_file)
+        .map(|opf| parse_opf_details(&opf))
+        .unwrap_or_default();
+
+    let creators = if details.creators.is_empty() {
+        // 解析失败时退回到 EpubDoc 的元数据
+        doc.metadata
+            .get("creator")
+            .map(|names| {
+                names
+                    .iter()
+                    .map(|name| Creator {
+                        name: name.trim().to_string(),
+                        role: None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    } else {
+        creators_with_roles(&details)
+    };
+
+    let mdata = |name: &str| {
+        doc.mdata(name)
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+
+    Ok(BookMetadata {
+        title: mdata("title"),
+        creators,
+        publisher: mdata("publisher"),
+        language: mdata("language"),
+        identifiers: details.identifiers,
+        date: mdata("date"),
+        description: mdata("description"),
+        subjects: doc
+            .metadata
+            .get("subject")
+            .map(|subjects| {
+                subjects
+                    .iter()
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+    })
+}
+
+// 保存元数据到书籍目录下的 metadata.json
+pub fn save_metadata(hash_dir: &Path, metadata: &BookMetadata) -> Result<(), String> {
+    let json_data = serde_json::to_string(metadata)
+        .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
+    std::fs::write(hash_dir.join("metadata.json"), json_data)
+        .map_err(|e| format!("Failed to write metadata file: {}", e))
+}
+
+// 加载书籍目录下的元数据，如果不存在则从epub文件中提取并保存
+pub fn load_or_extract_metadata(hash_dir: &Path, epub_path: &Path) -> BookMetadata {
+    let metadata_path = hash_dir.join("metadata.json");
+    if let Some(metadata) = std::fs::read_to_string(&metadata_path)
+        .ok()
+        .and_then(|json_data| serde_json::from_str(&json_data).ok())
+    {
+        return metadata;
+    }
+
+    match read_epub_metadata(epub_path) {
+        Ok(metadata) => {
+            if let Err(e) = save_metadata(hash_dir, &metadata) {
+                println!("{}", e);
+            }
+            metadata
+        }
+        Err(e) => {
+            println!("Failed to read metadata of {}: {}", epub_path.display(), e);
+            BookMetadata::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn creators(opf: &str) -> Vec<(String, Option<String>)> {
+        creators_with_roles(&parse_opf_details(opf))
+            .into_iter()
+            .map(|creator| (creator.name, creator.role))
+            .collect()
+    }
+
+    #[test]
+    fn parses_multiple_creators_with_opf_roles() {
+        let opf = r#"<package xmlns:dc="http://purl.org/dc/elements/1.1/"
+                xmlns:opf="http://www.idpf.org/2007/opf">
+              <metadata>
+                <dc:creator opf:role="aut"> Lu Xun </dc:creator>
+                <dc:creator opf:role="trl">Yang Xianyi</dc:creator>
+                <dc:creator></dc:creator>
+                <dc:creator>Gladys Yang</dc:creator>
+              </metadata>
+            </package>"#;
+        assert_eq!(
+            creators(opf),
+            [
+                ("Lu Xun".to_string(), Some("aut".to_string())),
+                ("Yang Xianyi".to_string(), Some("trl".to_string())),
+                ("Gladys Yang".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn epub3_roles_come_from_refines() {
+        let opf = r##"<package xmlns:dc="http://purl.org/dc/elements/1.1/">
+              <metadata>
+                <dc:creator id="author">Ann</dc:creator>
+                <meta refines="#author" property="role" scheme="marc:relators">aut</meta>
+                <dc:creator id="ill">Ben</dc:creator>
+                <meta refines="#ill" property="role">ill</meta>
+                <meta refines="#ill" property="file-as">Ben</meta>
+              </metadata>
+            </package>"##;
+        assert_eq!(
+            creators(opf),
+            [
+                ("Ann".to_string(), Some("aut".to_string())),
+                ("Ben".to_string(), Some("ill".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn classifies_identifiers() {
+        let opf = r#"<package xmlns:dc="http://purl.org/dc/elements/1.1/"
+                xmlns:opf="http://www.idpf.org/2007/opf">
+              <metadata>
+                <dc:identifier>urn:isbn:978-7-02-000220-1</dc:identifier>
+                <dc:identifier>URN:UUID:0B5E2F8A-1C3D-4E5F-8A9B-0C1D2E3F4A5B</dc:identifier>
+                <dc:identifier opf:scheme="ISBN">7020002201</dc:identifier>
+                <dc:identifier>0-306-40615-2</dc:identifier>
+                <dc:identifier opf:scheme="calibre">1234</dc:identifier>
+                <dc:identifier>store-42</dc:identifier>
+              </metadata>
+            </package>"#;
+        let identifiers: Vec<(Option<String>, String)> = parse_opf_details(opf)
+            .identifiers
+            .into_iter()
+            .map(|identifier| (identifier.scheme, identifier.value))
+            .collect();
+        let some = |s: &str| Some(s.to_string());
+        assert_eq!(
+            identifiers,
+            [
+                (some("isbn"), "978-7-02-000220-1".to_string()),
+                (
+                    some("uuid"),
+                    "0B5E2F8A-1C3D-4E5F-8A9B-0C1D2E3F4A5B".to_string()
+                ),
+                (some("isbn"), "7020002201".to_string()),
+                (some("isbn"), "0-306-40615-2".to_string()),
+                (some("calibre"), "1234".to_string()),
+                (None, "store-42".to_string()),
+            ]
+        );
+    }
+}
@@ -1,10 +1,47 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EpubFile {
-    pub cover: String,
+    pub id: String,              // Book ID, SHA-256 of the epub file
+    pub cover: String,           // Full size cover in its original format
+    pub thumbnail: String,       // rbook:// URL of the grid thumbnail, generated on request
+    pub thumbnail_small: String, // rbook:// URL of the list thumbnail, generated on request
+    pub has_custom_cover: bool,  // Whether the cover is a user override
     pub path: String,
     pub last_opened: Option<u64>, // Unix timestamp of last opened time
+    pub progress: Option<f64>,    // Reading progress percentage (0-100)
+    pub metadata: BookMetadata,   // Metadata parsed from the OPF
+    pub added_at: u64,            // Unix timestamp of import time
+    pub file_size: u64,           // Size of the epub file in bytes
+}
+
+// 书籍元数据，导入时从OPF中解析
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BookMetadata {
+    pub title: Option<String>,
+    pub creators: Vec<Creator>,
+    pub publisher: Option<String>,
+    pub language: Option<String>,
+    pub identifiers: Vec<Identifier>,
+    pub date: Option<String>, // Publication date as written in the OPF
+    pub description: Option<String>,
+    pub subjects: Vec<String>,
+}
+
+// 作者及其角色
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Creator {
+    pub name: String,
+    pub role: Option<String>, // MARC relator code, e.g. "aut", "trl", "ill"
+}
+
+// 书籍标识符
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Identifier {
+    pub scheme: Option<String>, // e.g. "isbn", "uuid"
+    pub value: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,6 +90,35 @@ impl BookMark {
         // 移除匹配页码的书签
         self.list.retain(|m| m.page != page);
     }
+
+    // 合并另一份书签列表，按CFI（没有CFI时按页码）判断是否为同一位置
+    // 同一位置保留已有书签，返回内容不同的冲突（已有书签, 合并进来的书签）
+    pub fn merge_marks(&mut self, marks: Vec<Mark>) -> Vec<(Mark, Mark)> {
+        let mut conflicts = Vec::new();
+        for mark in marks {
+            match self.list.iter().find(|m| m.same_position(&mark)) {
+                Some(existing) => {
+                    if existing.content != mark.content {
+                        conflicts.push((existing.clone(), mark));
+                    }
+                }
+                None => self.list.push(mark),
+            }
+        }
+        self.list.sort_by_key(|m| m.page);
+        conflicts
+    }
+}
+
+impl Mark {
+    // 是否为同一位置的书签，优先比较CFI
+    pub fn same_position(&self, other: &Mark) -> bool {
+        if self.cfi.is_empty() || other.cfi.is_empty() {
+            self.page == other.page
+        } else {
+            self.cfi == other.cfi
+        }
+    }
 }
 
 // 阅读器样式结构
@@ -74,3 +140,538 @@ impl Default for ReaderStyle {
         }
     }
 }
+
+// 批量导入中单个文件的结果状态
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Imported,       // 新导入
+    AlreadyPresent, // 书库中已存在
+    Failed,         // 导入失败
+}
+
+// 批量导入中单个文件的导入报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportResult {
+    pub origin_path: String,    // 原始文件路径
+    pub status: ImportStatus,   // 导入状态
+    pub book: Option<EpubFile>, // 导入成功或已存在时的书籍信息
+    pub error: Option<String>,  // 失败原因
+}
+
+// 书库索引中的一条书籍记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryRecord {
+    pub id: String,             // Book ID (name of the hash directory)
+    pub file_name: String,      // Name of the epub file inside the hash directory
+    pub cover_file: String,     // Name of the cover file inside the hash directory
+    pub metadata: BookMetadata, // Metadata parsed from the OPF
+    pub added_at: u64,          // Unix timestamp of import time
+    pub updated_at: u64,        // Unix timestamp of last record update
+    pub file_size: u64,         // Size of the epub file in bytes
+    #[serde(default)]
+    pub cover_version: u32, // Cover format version, older covers may lack thumbnails or have a wrong extension
+    #[serde(default)]
+    pub custom_cover_file: Option<String>, // User override inside the custom cover directory
+}
+
+// 持久化的书库索引 /com.rbook.app/config/library.json
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LibraryIndex {
+    pub books: Vec<LibraryRecord>,
+}
+
+// 回收站中的书籍
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedBook {
+    pub book: EpubFile,  // Book info, paths point into the trash directory
+    pub deleted_at: u64, // Unix timestamp of deletion
+}
+
+// 用户自定义书架
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collection {
+    pub id: String,
+    pub name: String,
+    pub book_ids: Vec<String>, // Ordered book IDs
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+// 所有书架 /com.rbook.app/config/collections.json
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CollectionStore {
+    pub collections: Vec<Collection>,
+}
+
+// 智能书架规则中可用的文本字段
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleTextField {
+    Title,
+    Author, // Any creator
+    Publisher,
+    Language,
+    Subject,    // Any subject
+    Identifier, // Any identifier (ISBN, UUID, ...)
+    FileName,
+}
+
+// 智能书架规则中可用的时间字段
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleTimeField {
+    LastOpened,
+    AddedAt,
+}
+
+// 智能书架规则，可以组合嵌套
+// 例如 {"op":"all","rules":[{"op":"equals","field":"language","value":"zh"},
+//       {"op":"within_days","field":"last_opened","days":30}]}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum SmartRule {
+    All { rules: Vec<SmartRule> }, // AND
+    Any { rules: Vec<SmartRule> }, // OR
+    Not { rule: Box<SmartRule> },
+    Equals { field: RuleTextField, value: String }, // Case-insensitive equality
+    Contains { field: RuleTextField, value: String }, // Case-insensitive substring
+    WithinDays { field: RuleTimeField, days: u64 }, // Timestamp is within the last N days
+    Finished, // Reading progress reached the end of the book
+}
+
+// 智能书架：保存的查询条件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmartShelf {
+    pub id: String,
+    pub name: String,
+    pub rule: SmartRule,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+// 所有智能书架 /com.rbook.app/config/smart_shelves.json
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SmartShelfStore {
+    pub shelves: Vec<SmartShelf>,
+}
+
+// 书库查询的排序字段
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LibrarySortKey {
+    #[default]
+    Title,
+    Author,
+    AddedAt,
+    LastOpened,
+    FileSize,
+}
+
+// 排序方向
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+// 书库分页查询条件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LibraryQuery {
+    pub sort_by: LibrarySortKey,
+    pub direction: SortDirection,
+    pub filter: Option<String>, // Matches title, author or file name, case-insensitive
+    pub offset: usize,
+    pub limit: Option<usize>, // None returns all remaining books
+}
+
+// 书库分页查询结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryPage {
+    pub total: usize, // Number of books matching the filter
+    pub books: Vec<EpubFile>,
+}
+
+// 监视文件夹配置 /com.rbook.app/config/watch_folders.json
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WatchFolderConfig {
+    pub folders: Vec<String>,
+}
+
+// 监视文件夹中已处理过的文件 /com.rbook.app/config/watch_scan.json
+// 启动时大小和修改时间都没有变化的文件不再重新计算哈希
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WatchScanCache {
+    pub files: HashMap<String, WatchedFile>, // Keyed by file path
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WatchedFile {
+    pub size: u64,
+    pub modified: u64, // Modification time in seconds since the epoch
+}
+
+// TXT 导入配置 /com.rbook.app/config/txt_import.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxtImportSettings {
+    pub chapter_patterns: Vec<String>, // Regexes matched against each trimmed line
+}
+
+impl Default for TxtImportSettings {
+    fn default() -> Self {
+        TxtImportSettings {
+            chapter_patterns: vec![
+                r"^第[0-9０-９零〇一二两三四五六七八九十百千万]+[章回节卷集部篇].{0,30}$".to_string(),
+                r"^(序章|序言|楔子|引子|前言|后记|尾声|番外).{0,30}$".to_string(),
+                r"^(?i)(chapter|part)\s+([0-9]+|[ivxlcdm]+)\b.{0,50}$".to_string(),
+                r"^(?i)(prologue|epilogue)\b.{0,50}$".to_string(),
+            ],
+        }
+    }
+}
+
+// 书库变化事件的内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryChangedEvent {
+    pub imported: Vec<EpubFile>, // Newly imported books
+}
+
+// 后台任务类型
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Import,
+    Reindex,
+    Export,
+    Restore,
+    IntegrityCheck,
+    Search,
+    IndexRebuild,
+    Migration,
+}
+
+// 后台任务状态
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+// 后台任务信息，状态变化时通过 job-progress 事件发送给前端
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobInfo {
+    pub id: String,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub total: usize,                      // Total number of steps, 0 if unknown
+    pub completed: usize,                  // Number of finished steps
+    pub message: Option<String>,           // Item currently being processed
+    pub error: Option<String>,             // Failure reason
+    pub result: Option<serde_json::Value>, // Job output once completed, partial if cancelled
+    pub created_at: u64,
+    pub finished_at: Option<u64>,
+}
+
+// epub中的图片资源，可以选作封面
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpubImage {
+    pub id: String,   // Manifest item id
+    pub path: String, // Path inside the epub archive
+    pub mime: String,
+}
+
+// 备份中的一个文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupFileEntry {
+    pub path: String, // Path inside the archive, e.g. "books/<id>/mark.json"
+    pub size: u64,
+    pub sha256: String,
+}
+
+// 书库备份清单，保存在备份压缩包的 manifest.json 中
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    pub app_version: String,
+    pub created_at: u64,
+    pub include_books: bool, // false for notes-only backups without epub files
+    pub files: Vec<BackupFileEntry>,
+}
+
+// 恢复备份时书签文件的合并策略
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeStrategy {
+    KeepLocal,    // Keep local files when both sides exist
+    KeepIncoming, // Overwrite local files with the backup
+    #[default]
+    Merge, // Merge bookmark lists by page/CFI, keep local for other files
+}
+
+// 合并书签时同一位置内容不同的冲突
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookmarkConflict {
+    pub book_id: String,
+    pub page: u32,
+    pub cfi: String,
+    pub local_content: String, // Kept
+    pub incoming_content: String,
+}
+
+// 恢复备份的结果报告
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RestoreReport {
+    pub restored_books: Vec<String>, // IDs of books that did not exist locally
+    pub skipped_books: Vec<String>,  // IDs of books whose EPUB is neither local nor in the backup
+    pub restored_files: usize,
+    pub skipped_files: usize, // Files kept local because of the strategy
+    pub conflicts: Vec<BookmarkConflict>,
+    pub errors: Vec<String>, // Files that failed verification or could not be written
+}
+
+// 书库检查发现的问题类型
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityIssueKind {
+    InvalidDirectory, // Directory under books/ whose name is not a book ID
+    MissingEpub,      // Orphan directory without an EPUB file
+    HashMismatch,     // EPUB content does not match the directory name
+    MissingCover,
+    CorruptFile, // JSON or timestamp file that cannot be parsed
+}
+
+// 书库检查发现的一个问题
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntegrityIssue {
+    pub kind: IntegrityIssueKind,
+    pub book_id: Option<String>,
+    pub path: String,
+    pub detail: String,
+    pub repaired: bool,
+}
+
+// 书库检查报告
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    pub checked_books: usize,
+    pub skipped_books: Vec<String>, // Book IDs left alone because they are being imported
+    pub pending_migration: Vec<String>, // Legacy MD5 book dirs not migrated yet
+    pub issues: Vec<IntegrityIssue>,
+    pub quarantine_dir: Option<String>, // Where corrupt files were moved, if any
+}
+
+// 判定为重复书籍的原因
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+    Identifier,  // Same normalized ISBN/UUID
+    TitleAuthor, // Similar title and same first author
+}
+
+// 一组重复的书籍
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    pub reasons: Vec<DuplicateReason>,
+    pub books: Vec<EpubFile>, // Sorted by added_at, oldest first
+}
+
+// 阅读进度，保存在书籍目录下的 progress.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadingProgress {
+    pub cfi: String,                  // Current position
+    pub percentage: f64,              // 0-100
+    pub chapter_href: Option<String>, // Href of the current spine item
+    pub updated_at: u64,              // Unix timestamp
+}
+
+// 一次阅读记录，保存在书籍目录下的 sessions.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadingSession {
+    pub id: String,
+    pub start: u64, // Unix timestamp
+    pub end: u64,   // Unix timestamp, extended while reading
+    pub start_cfi: String,
+    pub end_cfi: String,
+    pub pages_turned: u32,
+}
+
+// 阅读统计查询条件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadingStatsQuery {
+    pub days: Option<u64>,       // Only count the last N days, None for all time
+    pub utc_offset_minutes: i32, // Local timezone offset used to split days, e.g. 480 for UTC+8
+}
+
+// 一本书的阅读时间
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BookReadingTime {
+    pub book_id: String,
+    pub seconds: u64,
+    pub sessions: usize,
+    pub pages_turned: u64,
+    pub last_read: u64, // Unix timestamp
+}
+
+// 一天或一周的阅读时间
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeriodReadingTime {
+    pub start_date: String, // YYYY-MM-DD in local time, weeks start on Monday
+    pub seconds: u64,
+}
+
+// 阅读统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReadingStats {
+    pub total_seconds: u64,
+    pub per_book: Vec<BookReadingTime>, // Sorted by time spent, most first
+    pub per_day: Vec<PeriodReadingTime>,
+    pub per_week: Vec<PeriodReadingTime>,
+    pub current_streak: u32, // Consecutive days with reading up to today
+    pub longest_streak: u32,
+}
+
+// 目录条目，与前端的 TocItem 对应
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TocItem {
+    pub label: String,
+    pub href: String, // Path inside the epub with optional #fragment, empty for headings
+    pub level: usize, // 0 for top level entries
+    pub spine_index: Option<usize>, // Position of the target document in the spine
+    pub subitems: Vec<TocItem>,
+}
+
+// 目录的来源
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TocSource {
+    Nav,  // EPUB3 navigation document
+    Ncx,  // EPUB2 NCX
+    None, // The book has no table of contents
+}
+
+// 阅读顺序中的一项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpineEntry {
+    pub idref: String,
+    pub href: String, // Path inside the epub
+    pub mime: String,
+    pub linear: bool, // false for auxiliary content such as footnotes
+}
+
+// 书籍的目录和阅读顺序，缓存在书籍目录下的 navigation.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookNavigation {
+    pub toc: Vec<TocItem>,
+    pub toc_source: TocSource,
+    pub spine: Vec<SpineEntry>,
+}
+
+// 书内搜索条件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BookSearchQuery {
+    pub query: String,
+    pub regex: bool,
+    pub match_case: bool,           // Case-insensitive by default
+    pub match_diacritics: bool,     // "cafe" also matches "café" by default
+    pub max_results: Option<usize>, // Defaults to 1000
+}
+
+// 书内搜索的一条结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchMatch {
+    pub spine_index: usize,
+    pub href: String,            // Path of the chapter inside the epub
+    pub chapter: Option<String>, // Chapter title from the table of contents
+    pub cfi: String,             // Position of the match start
+    pub before: String,          // Snippet text before the match
+    pub matched: String,
+    pub after: String, // Snippet text after the match
+}
+
+// 书内搜索结果事件，每个章节发送一次
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookSearchEvent {
+    pub job_id: String,
+    pub matches: Vec<SearchMatch>,
+}
+
+// 全文索引中的一本书
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedBook {
+    pub id: String,
+    pub chapters: u32, // Number of indexed spine documents
+}
+
+// 全文倒排索引，保存在 /com.rbook.app/index/fulltext.json
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FullTextIndex {
+    pub version: u32,
+    pub next_key: u32,
+    pub books: HashMap<u32, IndexedBook>, // Short keys keep the postings small
+    pub terms: HashMap<String, Vec<(u32, u32, u32)>>, // term -> [(book key, spine index, count)]
+}
+
+// 全文搜索的一条结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FullTextHit {
+    pub book_id: String,
+    pub title: Option<String>,
+    pub score: f64,            // TF-IDF relevance, higher first
+    pub location: SearchMatch, // First match in the chapter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mark(page: u32, content: &str, cfi: &str) -> Mark {
+        Mark {
+            page,
+            content: content.to_string(),
+            width: 800,
+            height: 600,
+            cfi: cfi.to_string(),
+        }
+    }
+
+    #[test]
+    fn merge_marks_adds_new_positions_sorted_by_page() {
+        let mut bookmark = BookMark::new("book.epub".to_string());
+        bookmark.list.push(mark(3, "c", "/6/6!/4/2:0"));
+        let conflicts = bookmark.merge_marks(vec![
+            mark(1, "a", "/6/2!/4/2:0"),
+            mark(3, "c", "/6/6!/4/2:0"),
+            mark(2, "b", ""),
+        ]);
+        assert!(conflicts.is_empty());
+        let pages: Vec<u32> = bookmark.list.iter().map(|m| m.page).collect();
+        assert_eq!(pages, [1, 2, 3]);
+    }
+
+    #[test]
+    fn merge_marks_keeps_existing_mark_on_conflict() {
+        let mut bookmark = BookMark::new("book.epub".to_string());
+        bookmark.list.push(mark(1, "local", "/6/2!/4/2:0"));
+        bookmark.list.push(mark(5, "page", ""));
+        let conflicts = bookmark.merge_marks(vec![
+            mark(2, "incoming", "/6/2!/4/2:0"),
+            mark(5, "other", "/6/10!/4/2:0"),
+        ]);
+        assert_eq!(bookmark.list.len(), 2);
+        assert_eq!(bookmark.list[0].content, "local");
+        let contents: Vec<(&str, &str)> = conflicts
+            .iter()
+            .map(|(local, incoming)| (local.content.as_str(), incoming.content.as_str()))
+            .collect();
+        assert_eq!(contents, [("local", "incoming"), ("page", "other")]);
+    }
+}
@@ -0,0 +1,303 @@
+use crate::file::find_epub_in_dir;
+use crate::library::get_book_dir;
+use crate::model::{BookNavigation, SpineEntry, TocItem, TocSource};
+use crate::util::percent_decode;
+use epub::doc::{EpubDoc, NavPoint};
+use std::fs;
+use std::path::Path;
+use xml::reader::{EventReader, XmlEvent};
+
+// 目录和阅读顺序缓存在书籍目录下，epub内容不会变化（目录名即内容哈希），生成一次即可
+pub const NAVIGATION_FILE: &str = "navigation.json";
+
+// 将相对于 base_file 的链接解析为epub内的完整路径，保留 #片段
+fn resolve_href(base_file: &str, href: &str) -> String {
+    let (path, fragment) = match href.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (href, None),
+    };
+    let path = percent_decode(path).unwrap_or_else(|| path.to_string());
+
+    let mut parts: Vec<&str> = base_file.split('/').collect();
+    parts.pop();
+    if path.is_empty() {
+        // 只有片段时指向当前文件
+        parts = base_file.split('/').collect();
+    }
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    let resolved = parts
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("/");
+    match fragment {
+        Some(fragment) => format!("{}#{}", resolved, fragment),
+        None => resolved,
+    }
+}
+
+// 从OPF清单中查找EPUB3导航文档（properties 包含 nav）的ID
+fn find_nav_item_id(opf: &str) -> Option<String> {
+    for event in EventReader::new(opf.as_bytes()) {
+        match event {
+            Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) if name.local_name == "item" => {
+                let attr = |key: &str| {
+                    attributes
+                        .iter()
+                        .find(|a| a.name.local_name == key)
+                        .map(|a| a.value.clone())
+                };
+                let is_nav = attr("properties")
+                    .map(|p| p.split_whitespace().any(|p| p == "nav"))
+                    .unwrap_or(false);
+                if is_nav {
+                    return attr("id");
+                }
+            }
+            Err(_) => break,
+            _ => {}
+        }
+    }
+    None
+}
+
+// 解析EPUB3导航文档中 epub:type="toc" 的 <nav>，嵌套的 <ol><li> 对应目录层级
+// 没有链接的 <span> 标题 href 为空
+fn parse_nav_document(nav: &str, nav_path: &str) -> Option<Vec<TocItem>> {
+    // 尚未结束的 <li> 条目，结束时加入父条目或顶层
+    let mut open: Vec<TocItem> = Vec::new();
+    let mut toc: Vec<TocItem> = Vec::new();
+    let mut nav_depth = 0;
+    let mut in_toc = false;
+    let mut found = false;
+    let mut label: Option<String> = None;
+
+    for event in EventReader::new(nav.as_bytes()) {
+        match event.ok()? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                let attr = |key: &str| {
+                    attributes
+                        .iter()
+                        .find(|a| a.name.local_name == key)
+                        .map(|a| a.value.clone())
+                };
+                match name.local_name.as_str() {
+                    "nav" if in_toc => nav_depth += 1,
+                    "nav" => {
+                        let is_toc = attr("type")
+                            .map(|t| t.split_whitespace().any(|t| t == "toc"))
+                            .unwrap_or(false);
+                        if is_toc && !found {
+                            in_toc = true;
+                            found = true;
+                            nav_depth = 1;
+                        }
+                    }
+                    "li" if in_toc => open.push(TocItem {
+                        level: open.len(),
+                        ..Default::default()
+                    }),
+                    "a" | "span" if in_toc && label.is_none() => {
+                        if let Some(item) = open.last_mut() {
+                            if let Some(href) = attr("href") {
+                                item.href = resolve_href(nav_path, &href);
+                            }
+                            label = Some(String::new());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            XmlEvent::Characters(s) | XmlEvent::CData(s) => {
+                if let Some(label) = label.as_mut() {
+                    label.push_str(&s);
+                }
+            }
+            XmlEvent::EndElement { name } if in_toc => match name.local_name.as_str() {
+                "a" | "span" => {
+                    if let (Some(text), Some(item)) = (label.take(), open.last_mut()) {
+                        if item.label.is_empty() {
+                            item.label = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                        }
+                    }
+                }
+                "li" => {
+                    let Some(item) = open.pop() else {
+                        continue;
+                    };
+                    // 跳过既没有标题也没有子目录的空条目
+                    if item.label.is_empty() && item.subitems.is_empty() {
+                        continue;
+                    }
+                    match open.last_mut() {
+                        Some(parent) => parent.subitems.push(item),
+                        None => toc.push(item),
+                    }
+                }
+                "nav" => {
+                    nav_depth -= 1;
+                    if nav_depth == 0 {
+                        in_toc = false;
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    found.then_some(toc)
+}
+
+// 将epub库解析的NCX目录转换为目录条目
+fn toc_from_ncx(nav_points: &[NavPoint], level: usize) -> Vec<TocItem> {
+    nav_points
+        .iter()
+        .map(|point| TocItem {
+            label: point.label.trim().to_string(),
+            href: resolve_href("", &point.content.to_string_lossy().replace('\\', "/")),
+            level,
+            spine_index: None,
+            subitems: toc_from_ncx(&point.children, level + 1),
+        })
+        .collect()
+}
+
+// 为目录条目设置对应的阅读顺序位置
+fn assign_spine_index(items: &mut [TocItem], spine: &[SpineEntry]) {
+    for item in items {
+        let path = item.href.split('#').next().unwrap_or_default();
+        item.spine_index = spine.iter().position(|entry| entry.href == path);
+        assign_spine_index(&mut item.subitems, spine);
+    }
+}
+
+// 从epub文件中读取目录和阅读顺序，优先使用EPUB3导航文档，没有时使用NCX
+pub fn read_book_navigation(epub_path: &Path) -> Result<BookNavigation, String> {
+    let mut doc = EpubDoc::new(epub_path).map_err(|e| e.to_string())?;
+    let resources = doc.resources.clone();
+    let resource_href = |id: &str| {
+        resources
+            .get(id)
+            .map(|(path, mime)| (path.to_string_lossy().replace('\\', "/"), mime.clone()))
+    };
+
+    let spine: Vec<SpineEntry> = doc
+        .spine
+        .iter()
+        .filter_map(|item| {
+            let (href, mime) = resource_href(&item.idref)?;
+            Some(SpineEntry {
+                idref: item.idref.clone(),
+                href,
+                mime,
+                linear: item.linear,
+            })
+        })
+        .collect();
+    let ncx_toc = toc_from_ncx(&doc.toc, 0);
+
+    let root_file = doc.root_file.clone();
+    let nav_path = doc
+        .get_resource_str_by_path(&root_file)
+        .and_then(|opf| find_nav_item_id(&opf))
+        .and_then(|id| resource_href(&id).map(|(href, _)| href));
+    let nav_toc = nav_path.and_then(|nav_path| {
+        let nav = doc.get_resource_str_by_path(&nav_path)?;
+        parse_nav_document(&nav, &nav_path).filter(|toc| !toc.is_empty())
+    });
+
+    let (mut toc, toc_source) = match nav_toc {
+        Some(toc) => (toc, TocSource::Nav),
+        None if !ncx_toc.is_empty() => (ncx_toc, TocSource::Ncx),
+        None => (Vec::new(), TocSource::None),
+    };
+    assign_spine_index(&mut toc, &spine);
+
+    Ok(BookNavigation {
+        toc,
+        toc_source,
+        spine,
+    })
+}
+
+// 读取书籍目录中缓存的目录和阅读顺序，没有缓存时从epub文件中解析并缓存
+pub fn load_book_navigation(hash_dir: &Path) -> Result<BookNavigation, String> {
+    let cache_path = hash_dir.join(NAVIGATION_FILE);
+    if let Some(navigation) = fs::read_to_string(&cache_path)
+        .ok()
+        .and_then(|json_data| serde_json::from_str(&json_data).ok())
+    {
+        return Ok(navigation);
+    }
+
+    let epub_path = find_epub_in_dir(hash_dir)
+        .ok_or_else(|| format!("No epub file found in {}", hash_dir.display()))?;
+    let navigation = read_book_navigation(&epub_path)?;
+    let json_data = serde_json::to_string(&navigation)
+        .map_err(|e| format!("Failed to serialize navigation: {}", e))?;
+    if let Err(e) = fs::write(&cache_path, json_data) {
+        println!(
+            "Failed to cache navigation in {}: {}",
+            hash_dir.display(),
+            e
+        );
+    }
+    Ok(navigation)
+}
+
+// 获取书籍的目录和阅读顺序，可以在阅读器渲染书籍之前显示目录
+pub async fn get_book_navigation(
+    app_handle: &tauri::AppHandle,
+    id: &str,
+) -> Result<BookNavigation, String> {
+    let book_dir = get_book_dir(app_handle, id)?;
+    if !book_dir.exists() {
+        return Err(format!("Book not found: {}", id));
+    }
+    tokio::task::spawn_blocking(move || load_book_navigation(&book_dir))
+        .await
+        .map_err(|e| format!("Failed to read navigation: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_href_is_relative_to_base_file() {
+        assert_eq!(
+            resolve_href("OEBPS/nav.xhtml", "text/ch1.xhtml#s1"),
+            "OEBPS/text/ch1.xhtml#s1"
+        );
+        assert_eq!(resolve_href("nav.xhtml", "./ch1.xhtml"), "ch1.xhtml");
+    }
+
+    #[test]
+    fn resolve_href_handles_parent_dirs_and_escapes() {
+        assert_eq!(
+            resolve_href("OEBPS/text/nav.xhtml", "../ch%201.xhtml"),
+            "OEBPS/ch 1.xhtml"
+        );
+        assert_eq!(resolve_href("nav.xhtml", "../../ch1.xhtml"), "ch1.xhtml");
+    }
+
+    #[test]
+    fn resolve_href_with_only_fragment_points_to_base_file() {
+        assert_eq!(
+            resolve_href("OEBPS/nav.xhtml", "#toc"),
+            "OEBPS/nav.xhtml#toc"
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use image::{ImageFormat, Rgba, RgbaImage};
+use std::io::Cursor;
+use std::sync::OnceLock;
+
+// 生成封面的尺寸
+const COVER_WIDTH: u32 = 600;
+const COVER_HEIGHT: u32 = 900;
+// 文字左右留白
+const TEXT_MARGIN: f32 = 60.0;
+const TITLE_SIZE: f32 = 58.0;
+const AUTHOR_SIZE: f32 = 32.0;
+const MAX_TITLE_LINES: usize = 6;
+
+// 随程序打包的字体，保证在任何系统上都能绘制书名（拉丁、希腊、西里尔字母等）
+static BUNDLED_FONT: &[u8] = include_bytes!("../resources/fonts/DejaVuSans.ttf");
+
+// 打包字体不含中日韩字形，系统中存在这些字体时用于补充缺失的字形
+const CJK_FALLBACK_FONTS: &[&str] = &[
+    // Windows
+    "C:\\Windows\\Fonts\\msyh.ttc",
+    "C:\\Windows\\Fonts\\simhei.ttf",
+    // macOS
+    "/System/Library/Fonts/PingFang.ttc",
+    "/System/Library/Fonts/STHeiti Medium.ttc",
+    // Linux
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+];
+
+// 加载字体，打包字体始终排在第一位，只在第一次生成封面时读取
+fn load_fonts() -> &'static [FontVec] {
+    static FONTS: OnceLock<Vec<FontVec>> = OnceLock::new();
+    FONTS.get_or_init(|| {
+        let bundled = FontVec::try_from_vec(BUNDLED_FONT.to_vec())
+            .expect("bundled placeholder font is valid");
+        std::iter::once(bundled)
+            .chain(
+                CJK_FALLBACK_FONTS
+                    .iter()
+                    .filter_map(|path| std::fs::read(path).ok())
+                    .filter_map(|data| FontVec::try_from_vec_and_index(data, 0).ok()),
+            )
+            .collect()
+    })
+}
+
+// 查找包含该字符字形的字体
+fn font_for(fonts: &[FontVec], c: char) -> Option<&FontVec> {
+    fonts.iter().find(|font| font.glyph_id(c).0 != 0)
+}
+
+// HSL 转 RGB，h 取值 0-360，s/l 取值 0-1
+fn hsl_to_rgb(h: f32, s: f32, l: f32) -> Rgba<u8> {
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
+    let m = l - c / 2.0;
+    let (r, g, b) = match h as u32 {
+        0..=59 => (c, x, 0.0),
+        60..=119 => (x, c, 0.0),
+        120..=179 => (0.0, c, x),
+        180..=239 => (0.0, x, c),
+        240..=299 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let to_u8 = |v: f32| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    Rgba([to_u8(r), to_u8(g), to_u8(b), 255])
+}
+
+// 根据书籍ID生成固定的配色（背景色、装饰色）
+fn palette(id: &str) -> (Rgba<u8>, Rgba<u8>) {
+    let seed = id
+        .bytes()
+        .fold(0u32, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u32));
+    let hue = (seed % 360) as f32;
+    let saturation = 0.35 + ((seed >> 9) % 30) as f32 / 100.0;
+    (
+        hsl_to_rgb(hue, saturation, 0.30),
+        hsl_to_rgb((hue + 30.0) % 360.0, saturation, 0.62),
+    )
+}
+
+// 填充矩形
+fn fill_rect(image: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32, color: Rgba<u8>) {
+    for py in y..(y + height).min(image.height()) {
+        for px in x..(x + width).min(image.width()) {
+            image.put_pixel(px, py, color);
+        }
+    }
+}
+
+// 计算文字宽度
+fn text_width(fonts: &[FontVec], text: &str, size: f32) -> f32 {
+    text.chars()
+        .filter_map(|c| font_for(fonts, c).map(|font| (font, c)))
+        .map(|(font, c)| {
+            font.as_scaled(PxScale::from(size))
+                .h_advance(font.glyph_id(c))
+        })
+        .sum()
+}
+
+// 将文字拆分为换行单元：中日韩字符单独成为一个单元，其他文字按空格分词
+fn split_tokens(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    for c in text.chars() {
+        let is_cjk = matches!(c as u32, 0x2E80..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF | 0xFF00..=0xFFEF);
+        if is_cjk {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+            tokens.push(c.to_string());
+        } else {
+            word.push(c);
+            if c.is_whitespace() {
+                tokens.push(std::mem::take(&mut word));
+            }
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+// 按最大宽度折行，超过最大行数时末尾加省略号
+fn wrap_text(
+    fonts: &[FontVec],
+    text: &str,
+    size: f32,
+    max_width: f32,
+    max_lines: usize,
+) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for token in split_tokens(text) {
+        let candidate = format!("{}{}", line, token);
+        if text_width(fonts, candidate.trim_end(), size) <= max_width {
+            line = candidate;
+            continue;
+        }
+        if !line.trim().is_empty() {
+            lines.push(line.trim().to_string());
+        }
+        // 单个单词超过一行时按字符拆分
+        line = String::new();
+        for c in token.chars() {
+            let candidate = format!("{}{}", line, c);
+            if text_width(fonts, &candidate, size) > max_width && !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+                line.push(c);
+            } else {
+                line = candidate;
+            }
+        }
+    }
+    if !line.trim().is_empty() {
+        lines.push(line.trim().to_string());
+    }
+
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            while !last.is_empty() && text_width(fonts, &format!("{}…", last), size) > max_width {
+                last.pop();
+            }
+            last.push('…');
+        }
+    }
+    lines
+}
+
+// 在指定基线位置水平居中绘制一行文字
+fn draw_centered_line(
+    image: &mut RgbaImage,
+    fonts: &[FontVec],
+    text: &str,
+    size: f32,
+    baseline: f32,
+    color: Rgba<u8>,
+) {
+    let scale = PxScale::from(size);
+    let mut caret = (image.width() as f32 - text_width(fonts, text, size)) / 2.0;
+    for c in text.chars() {
+        let Some(font) = font_for(fonts, c) else {
+            continue;
+        };
+        let glyph_id = font.glyph_id(c);
+        let glyph = glyph_id.with_scale_and_position(scale, point(caret, baseline));
+        if let Some(outlined) = font.outline_glyph(glyph) {
+            let bounds = outlined.px_bounds();
+            outlined.draw(|gx, gy, coverage| {
+                let x = bounds.min.x as i32 + gx as i32;
+                let y = bounds.min.y as i32 + gy as i32;
+                if x < 0 || y < 0 || x >= image.width() as i32 || y >= image.height() as i32 {
+                    return;
+                }
+                let pixel = image.get_pixel_mut(x as u32, y as u32);
+                for i in 0..3 {
+                    pixel.0[i] =
+                        (pixel.0[i] as f32 * (1.0 - coverage) + color.0[i] as f32 * coverage) as u8;
+                }
+            });
+        }
+        caret += font.as_scaled(scale).h_advance(glyph_id);
+    }
+}
+
+// 绘制多行文字，返回最后一行之后的基线位置
+fn draw_lines(
+    image: &mut RgbaImage,
+    fonts: &[FontVec],
+    lines: &[String],
+    size: f32,
+    top: f32,
+    color: Rgba<u8>,
+) -> f32 {
+    let line_height = size * 1.3;
+    let mut baseline = top + size;
+    for line in lines {
+        draw_centered_line(image, fonts, line, size, baseline, color);
+        baseline += line_height;
+    }
+    baseline
+}
+
+// 为没有可用封面的书籍生成封面：根据书籍ID确定配色，绘制书名和作者
+// 返回PNG图片数据
+pub fn render_placeholder_cover(
+    id: &str,
+    title: &str,
+    author: Option<&str>,
+) -> Result<Vec<u8>, String> {
+    let (background, accent) = palette(id);
+    let text_color = Rgba([250, 247, 240, 255]);
+    let mut image = RgbaImage::from_pixel(COVER_WIDTH, COVER_HEIGHT, background);
+
+    // 顶部、底部装饰色带和左侧书脊
+    fill_rect(&mut image, 0, 0, COVER_WIDTH, 28, accent);
+    fill_rect(&mut image, 0, COVER_HEIGHT - 28, COVER_WIDTH, 28, accent);
+    fill_rect(&mut image, 0, 0, 14, COVER_HEIGHT, accent);
+
+    let fonts = load_fonts();
+    let max_width = COVER_WIDTH as f32 - TEXT_MARGIN * 2.0;
+    let title_lines = wrap_text(fonts, title.trim(), TITLE_SIZE, max_width, MAX_TITLE_LINES);
+    let bottom = draw_lines(
+        &mut image,
+        fonts,
+        &title_lines,
+        TITLE_SIZE,
+        160.0,
+        text_color,
+    );
+
+    // 书名与作者之间的分隔线
+    let divider_y = (bottom - TITLE_SIZE * 0.3 + 24.0) as u32;
+    fill_rect(&mut image, COVER_WIDTH / 2 - 40, divider_y, 80, 4, accent);
+
+    if let Some(author) = author.map(str::trim).filter(|a| !a.is_empty()) {
+        let author_lines = wrap_text(fonts, author, AUTHOR_SIZE, max_width, 2);
+        draw_lines(
+            &mut image,
+            fonts,
+            &author_lines,
+            AUTHOR_SIZE,
+            divider_y as f32 + 36.0,
+            text_color,
+        );
+    }
+
+    let mut buffer = Cursor::new(Vec::new());
+    image
+        .write_to(&mut buffer, ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode generated cover: {}", e))?;
+    Ok(buffer.into_inner())
+}
@@ -0,0 +1,67 @@
+use crate::file::get_current_timestamp;
+use crate::library::get_book_dir;
+use crate::model::ReadingProgress;
+use std::fs;
+use std::path::Path;
+use tauri::AppHandle;
+
+// 阅读进度保存在书籍目录下，与书签分开
+pub const PROGRESS_FILE: &str = "progress.json";
+
+// 进度达到该百分比时视为已读完（最后一页通常不会正好是100%）
+pub const FINISHED_PERCENTAGE: f64 = 99.0;
+
+// 读取书籍目录中的阅读进度
+pub fn read_progress(hash_dir: &Path) -> Option<ReadingProgress> {
+    fs::read_to_string(hash_dir.join(PROGRESS_FILE))
+        .ok()
+        .and_then(|json_data| serde_json::from_str(&json_data).ok())
+}
+
+// 获取阅读进度百分比，用于书库显示
+pub fn get_progress_percentage(hash_dir: &Path) -> Option<f64> {
+    read_progress(hash_dir).map(|progress| progress.percentage)
+}
+
+// 写入阅读进度
+pub fn write_progress(hash_dir: &Path, progress: &ReadingProgress) -> Result<(), String> {
+    let json_data = serde_json::to_string(progress)
+        .map_err(|e| format!("Failed to serialize reading progress: {}", e))?;
+    fs::write(hash_dir.join(PROGRESS_FILE), json_data)
+        .map_err(|e| format!("Failed to write reading progress: {}", e))
+}
+
+// 保存阅读进度，翻页或关闭书籍时调用
+pub async fn save_reading_progress(
+    app_handle: &AppHandle,
+    id: &str,
+    cfi: String,
+    percentage: f64,
+    chapter_href: Option<String>,
+) -> Result<ReadingProgress, String> {
+    let book_dir = get_book_dir(app_handle, id)?;
+    if !book_dir.exists() {
+        return Err(format!("Book not found: {}", id));
+    }
+    let progress = ReadingProgress {
+        cfi,
+        percentage: if percentage.is_finite() {
+            percentage.clamp(0.0, 100.0)
+        } else {
+            0.0
+        },
+        chapter_href,
+        updated_at: get_current_timestamp()?,
+    };
+    write_progress(&book_dir, &progress)?;
+    Ok(progress)
+}
+
+// 获取阅读进度，没有阅读记录时返回空
+pub async fn get_reading_progress(
+    app_handle: &AppHandle,
+    id: &str,
+) -> Result<Option<ReadingProgress>, String> {
+    let book_dir = get_book_dir(app_handle, id)?;
+    Ok(read_progress(&book_dir))
+}
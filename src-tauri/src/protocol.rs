@@ -0,0 +1,333 @@
+use crate::cover::{ensure_cover_image, CoverSize};
+use crate::file::find_epub_in_dir;
+use crate::library::get_book_dir;
+use crate::trash::get_trashed_book_dir;
+use crate::util::percent_decode;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, UriSchemeContext, UriSchemeResponder, Wry};
+use zip::ZipArchive;
+
+// 书籍内容协议名称
+// macOS/Linux: rbook://book/<id>/<epub内路径>
+// Windows/Android: http://rbook.localhost/book/<id>/<epub内路径>
+// 封面: rbook://cover/<id>/<grid|list|full>
+pub const BOOK_PROTOCOL: &str = "rbook";
+
+// 按条目头中的大小预分配内存的上限，条目头可能被篡改
+const MAX_PREALLOCATION: u64 = 16 << 20;
+
+// 协议请求的资源
+enum ProtocolResource {
+    Book { id: String, resource_path: String },
+    Cover { id: String, size: CoverSize },
+}
+
+// 生成协议地址，各平台的自定义协议地址格式不同
+pub fn protocol_url(path: &str) -> String {
+    if cfg!(any(windows, target_os = "android")) {
+        format!("http://{}.localhost/{}", BOOK_PROTOCOL, path)
+    } else {
+        format!("{}://{}", BOOK_PROTOCOL, path)
+    }
+}
+
+// 书籍内容的根地址，阅读器按解压后的目录加载书籍
+// epub文件保存在以书籍ID命名的目录中
+pub fn book_url(epub_path: &str) -> Result<String, String> {
+    let id = Path::new(epub_path)
+        .parent()
+        .and_then(|dir| dir.file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Not a library book: {}", epub_path))?;
+    Ok(protocol_url(&format!("book/{}/", id)))
+}
+
+// 书籍封面的地址，前端直接用作图片地址
+pub fn cover_url(id: &str, size: CoverSize) -> String {
+    let size = match size {
+        CoverSize::Grid => "grid",
+        CoverSize::List => "list",
+        CoverSize::Full => "full",
+    };
+    protocol_url(&format!("cover/{}/{}", id, size))
+}
+
+// 按扩展名获取EPUB中资源的MIME类型
+fn mime_type(path: &str) -> &'static str {
+    let extension = path
+        .rsplit('.')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "xhtml" | "xht" => "application/xhtml+xml",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ncx" => "application/x-dtbncx+xml",
+        "opf" => "application/oebps-package+xml",
+        "smil" => "application/smil+xml",
+        "xml" => "application/xml",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "mp4" => "video/mp4",
+        "txt" => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+// 从请求地址中解析请求的资源
+fn parse_protocol_uri(host: Option<&str>, path: &str) -> Option<ProtocolResource> {
+    let path = path.trim_start_matches('/');
+    let (route, path) = match host {
+        Some(host @ ("book" | "cover")) => (host, path),
+        _ => path.split_once('/')?,
+    };
+    let (id, rest) = path.split_once('/')?;
+    let id = id.to_string();
+    match route {
+        "book" => {
+            let resource_path = percent_decode(rest)?;
+            if resource_path.is_empty() || resource_path.split('/').any(|part| part == "..") {
+                return None;
+            }
+            Some(ProtocolResource::Book { id, resource_path })
+        }
+        "cover" => {
+            let size = match rest {
+                "grid" => CoverSize::Grid,
+                "list" => CoverSize::List,
+                "full" => CoverSize::Full,
+                _ => return None,
+            };
+            Some(ProtocolResource::Cover { id, size })
+        }
+        _ => None,
+    }
+}
+
+// 解析 Range 请求头，只支持单个范围，返回 [start, end]（包含end）
+fn parse_range(range: &str, size: u64) -> Option<(u64, u64)> {
+    if size == 0 {
+        return None;
+    }
+    let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            // bytes=-500 表示最后500个字节
+            let suffix: u64 = suffix.parse().ok()?;
+            (size.saturating_sub(suffix), size - 1)
+        }
+        (start, "") => (start.parse().ok()?, size - 1),
+        (start, end) => (start.parse().ok()?, end.parse::<u64>().ok()?.min(size - 1)),
+    };
+    (start <= end && end < size).then_some((start, end))
+}
+
+// 生成错误响应
+fn error_response(status: StatusCode, message: String) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .body(message.into_bytes())
+        .unwrap()
+}
+
+// 从EPUB中读取资源，压缩的条目无法随机访问，Range 请求时跳过前面的字节
+fn read_book_resource(
+    app_handle: &AppHandle,
+    id: &str,
+    resource_path: &str,
+    range: Option<&str>,
+) -> Result<Response<Vec<u8>>, (StatusCode, String)> {
+    let not_found = |message: String| (StatusCode::NOT_FOUND, message);
+    let book_dir = get_book_dir(app_handle, id).map_err(not_found)?;
+    let epub_path =
+        find_epub_in_dir(&book_dir).ok_or_else(|| not_found(format!("Book not found: {}", id)))?;
+    let file = File::open(&epub_path)
+        .map_err(|e| not_found(format!("Failed to open epub file: {}", e)))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read epub file: {}", e),
+        )
+    })?;
+    let mut entry = archive
+        .by_name(resource_path)
+        .map_err(|_| not_found(format!("Resource not found: {}", resource_path)))?;
+    let size = entry.size();
+    let read_error = |e: io::Error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read resource: {}", e),
+        )
+    };
+
+    let response = Response::builder()
+        .header(header::CONTENT_TYPE, mime_type(resource_path))
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*");
+
+    // 不支持多个范围，按 RFC 9110 返回完整内容
+    match range.filter(|range| !range.contains(',')) {
+        Some(range) => {
+            let Some((start, end)) = parse_range(range, size) else {
+                return Ok(Response::builder()
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+                    .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+                    .body(Vec::new())
+                    .unwrap());
+            };
+            io::copy(&mut (&mut entry).take(start), &mut io::sink()).map_err(read_error)?;
+            let mut data = Vec::with_capacity((end - start + 1).min(MAX_PREALLOCATION) as usize);
+            entry
+                .take(end - start + 1)
+                .read_to_end(&mut data)
+                .map_err(read_error)?;
+            Ok(response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", start, end, size),
+                )
+                .header(header::CONTENT_LENGTH, data.len())
+                .body(data)
+                .unwrap())
+        }
+        None => {
+            let mut data = Vec::with_capacity(size.min(MAX_PREALLOCATION) as usize);
+            entry.read_to_end(&mut data).map_err(read_error)?;
+            Ok(response
+                .status(StatusCode::OK)
+                .header(header::CONTENT_LENGTH, data.len())
+                .body(data)
+                .unwrap())
+        }
+    }
+}
+
+// 读取封面图片，缩略图不存在时先生成
+fn read_cover(
+    app_handle: &AppHandle,
+    id: &str,
+    size: CoverSize,
+) -> Result<Response<Vec<u8>>, (StatusCode, String)> {
+    let not_found = |message: String| (StatusCode::NOT_FOUND, message);
+    let mut book_dir = get_book_dir(app_handle, id).map_err(not_found)?;
+    if !book_dir.is_dir() {
+        // 回收站中的书籍使用同一个封面地址
+        book_dir = get_trashed_book_dir(app_handle, id).map_err(not_found)?;
+    }
+    if !book_dir.is_dir() {
+        return Err(not_found(format!("Book not found: {}", id)));
+    }
+    let cover_path = ensure_cover_image(&book_dir, size).map_err(not_found)?;
+    let data = std::fs::read(&cover_path).map_err(|e| not_found(e.to_string()))?;
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(
+            header::CONTENT_TYPE,
+            mime_type(&cover_path.to_string_lossy()),
+        )
+        // 设置自定义封面后地址不变，需要重新验证
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .header(header::CONTENT_LENGTH, data.len())
+        .body(data)
+        .unwrap())
+}
+
+// 处理书籍内容请求，阅读器按需加载正在渲染的章节和图片，而不是一次传输整个epub文件
+pub fn handle_book_protocol(
+    ctx: UriSchemeContext<'_, Wry>,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let app_handle = ctx.app_handle().clone();
+    let uri = request.uri();
+    let Some(resource) = parse_protocol_uri(uri.host(), uri.path()) else {
+        responder.respond(error_response(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid book resource: {}", uri),
+        ));
+        return;
+    };
+    let range = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+
+    // 解压在后台线程进行，不阻塞webview
+    tauri::async_runtime::spawn_blocking(move || {
+        let response = match resource {
+            ProtocolResource::Book { id, resource_path } => {
+                read_book_resource(&app_handle, &id, &resource_path, range.as_deref())
+            }
+            ProtocolResource::Cover { id, size } => read_cover(&app_handle, &id, size),
+        }
+        .unwrap_or_else(|(status, message)| error_response(status, message));
+        responder.respond(response);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_handles_open_and_suffix_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=500-", 1000), Some((500, 999)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=-2000", 1000), Some((0, 999)));
+        assert_eq!(parse_range("bytes=900-5000", 1000), Some((900, 999)));
+    }
+
+    #[test]
+    fn parse_range_rejects_unsatisfiable_ranges() {
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=5-1", 1000), None);
+        assert_eq!(parse_range("bytes=0-1", 0), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+        assert_eq!(parse_range("bytes=a-", 1000), None);
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), None);
+    }
+
+    #[test]
+    fn parse_protocol_uri_decodes_book_resources() {
+        let resource = parse_protocol_uri(Some("book"), "/abc/OEBPS/ch%201.xhtml");
+        assert!(matches!(
+            resource,
+            Some(ProtocolResource::Book { id, resource_path })
+                if id == "abc" && resource_path == "OEBPS/ch 1.xhtml"
+        ));
+        // Windows/Android 上路由在路径中
+        let resource = parse_protocol_uri(Some("rbook.localhost"), "/cover/abc/grid");
+        assert!(matches!(
+            resource,
+            Some(ProtocolResource::Cover { id, size: CoverSize::Grid }) if id == "abc"
+        ));
+    }
+
+    #[test]
+    fn parse_protocol_uri_rejects_parent_paths() {
+        assert!(parse_protocol_uri(Some("book"), "/abc/../secret").is_none());
+        assert!(parse_protocol_uri(Some("book"), "/abc/").is_none());
+        assert!(parse_protocol_uri(Some("cover"), "/abc/huge").is_none());
+    }
+}
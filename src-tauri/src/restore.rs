@@ -0,0 +1,367 @@
+use crate::backup::{BACKUP_CONFIG_FILES, BACKUP_FORMAT_VERSION, BACKUP_MANIFEST_FILE};
+use crate::collection::COLLECTION_LOCK;
+use crate::file::find_epub_in_dir;
+use crate::fulltext::index_book_in_background;
+use crate::jobs::{spawn_job, JobHandle};
+use crate::library::{get_books_dir, reconcile_library_index};
+use crate::model::{
+    BackupFileEntry, BackupManifest, BookMark, BookmarkConflict, JobKind, MergeStrategy,
+    RestoreReport,
+};
+use crate::smart_shelf::SMART_SHELF_LOCK;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use tauri::AppHandle;
+use tauri::Manager;
+use zip::ZipArchive;
+
+// 备份中文件的去向
+enum RestoreTarget {
+    Bookmark { book_id: String, path: PathBuf }, // books/<id>/mark.json，需要合并
+    BookFile { book_id: String, path: PathBuf }, // books/<id>/ 下的其他文件
+    Config { path: PathBuf },                    // config/ 下的配置文件
+}
+
+// 解析备份中的文件路径，拒绝不在 books/ 或 config/ 下的路径，防止写到应用目录之外
+fn resolve_target(app_dir: &Path, books_dir: &Path, archive_path: &str) -> Option<RestoreTarget> {
+    let parts: Vec<&str> = archive_path.split('/').collect();
+    if parts
+        .iter()
+        .any(|p| p.is_empty() || *p == "." || *p == ".." || p.contains('\\') || p.contains(':'))
+    {
+        return None;
+    }
+    match parts.as_slice() {
+        ["config", file] if BACKUP_CONFIG_FILES.contains(file) => Some(RestoreTarget::Config {
+            path: app_dir.join("config").join(file),
+        }),
+        ["books", id, rest @ ..] if !rest.is_empty() => {
+            if !id.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            let path = rest
+                .iter()
+                .fold(books_dir.join(id), |path, part| path.join(part));
+            if rest == ["mark.json"] {
+                Some(RestoreTarget::Bookmark {
+                    book_id: id.to_string(),
+                    path,
+                })
+            } else {
+                Some(RestoreTarget::BookFile {
+                    book_id: id.to_string(),
+                    path,
+                })
+            }
+        }
+        _ => None,
+    }
+}
+
+// 备份中 books/<id>/ 下的epub文件返回书籍ID
+fn archived_epub_id(archive_path: &str) -> Option<&str> {
+    let parts: Vec<&str> = archive_path.split('/').collect();
+    match parts.as_slice() {
+        ["books", id, file] if file.to_lowercase().ends_with(".epub") => Some(id),
+        _ => None,
+    }
+}
+
+// 备份中包含epub文件的书籍ID，epub的哈希值必须与书籍ID一致
+fn books_in_archive(manifest: &BackupManifest) -> HashSet<String> {
+    manifest
+        .files
+        .iter()
+        .filter_map(|entry| {
+            archived_epub_id(&entry.path)
+                .filter(|id| entry.sha256.eq_ignore_ascii_case(id))
+                .map(|id| id.to_string())
+        })
+        .collect()
+}
+
+// 写入配置文件时持有所属模块的读写锁，避免与正在进行的修改互相覆盖
+fn lock_config_file(path: &Path) -> Option<MutexGuard<'static, ()>> {
+    let lock: &'static Mutex<()> = match path.file_name()?.to_str()? {
+        "collections.json" => &COLLECTION_LOCK,
+        "smart_shelves.json" => &SMART_SHELF_LOCK,
+        _ => return None,
+    };
+    Some(lock.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+// 读取并校验备份清单
+fn read_manifest(archive: &mut ZipArchive<File>) -> Result<BackupManifest, String> {
+    let mut json_data = String::new();
+    archive
+        .by_name(BACKUP_MANIFEST_FILE)
+        .map_err(|_| "Backup manifest not found, not a library backup".to_string())?
+        .read_to_string(&mut json_data)
+        .map_err(|e| format!("Failed to read backup manifest: {}", e))?;
+    let manifest: BackupManifest =
+        serde_json::from_str(&json_data).map_err(|e| format!("Invalid backup manifest: {}", e))?;
+    if manifest.format_version > BACKUP_FORMAT_VERSION {
+        return Err(format!(
+            "Backup format version {} is newer than supported version {}",
+            manifest.format_version, BACKUP_FORMAT_VERSION
+        ));
+    }
+    Ok(manifest)
+}
+
+// 将备份中的文件解压到临时文件，同时校验大小和 SHA-256
+fn extract_verified(
+    archive: &mut ZipArchive<File>,
+    entry: &BackupFileEntry,
+    temp_path: &Path,
+) -> Result<(), String> {
+    let mut zip_file = archive.by_name(&entry.path).map_err(|e| {
+        format!(
+            "{} is listed in the manifest but missing: {}",
+            entry.path, e
+        )
+    })?;
+    if let Some(parent) = temp_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let mut out =
+        File::create(temp_path).map_err(|e| format!("Failed to create temp file: {}", e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let read = zip_file
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {}: {}", entry.path, e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        out.write_all(&buffer[..read])
+            .map_err(|e| format!("Failed to write {}: {}", temp_path.display(), e))?;
+        size += read as u64;
+    }
+
+    if size != entry.size || format!("{:x}", hasher.finalize()) != entry.sha256 {
+        let _ = fs::remove_file(temp_path);
+        return Err(format!(
+            "{} does not match the manifest checksum",
+            entry.path
+        ));
+    }
+    Ok(())
+}
+
+// 恢复书签文件，book_path 改写为本机路径
+fn restore_bookmark(
+    book_id: &str,
+    temp_path: &Path,
+    dest_path: &Path,
+    strategy: MergeStrategy,
+    report: &mut RestoreReport,
+) -> Result<(), String> {
+    let incoming: BookMark = fs::read_to_string(temp_path)
+        .ok()
+        .and_then(|json_data| serde_json::from_str(&json_data).ok())
+        .ok_or_else(|| format!("Invalid bookmark file for book {}", book_id))?;
+    let local: Option<BookMark> = fs::read_to_string(dest_path)
+        .ok()
+        .and_then(|json_data| serde_json::from_str(&json_data).ok());
+
+    let book_dir = dest_path.parent().ok_or("Failed to get book directory")?;
+    fs::create_dir_all(book_dir).map_err(|e| format!("Failed to create directory: {}", e))?;
+    let file_name = Path::new(&incoming.book_path.replace('\\', "/"))
+        .file_name()
+        .map(|name| name.to_os_string());
+
+    let mut bookmark = match (local, strategy) {
+        (Some(_), MergeStrategy::KeepLocal) => {
+            report.skipped_files += 1;
+            return Ok(());
+        }
+        (Some(mut local), MergeStrategy::Merge) => {
+            let conflicts = local.merge_marks(incoming.list);
+            report
+                .conflicts
+                .extend(
+                    conflicts
+                        .into_iter()
+                        .map(|(local, incoming)| BookmarkConflict {
+                            book_id: book_id.to_string(),
+                            page: incoming.page,
+                            cfi: incoming.cfi,
+                            local_content: local.content,
+                            incoming_content: incoming.content,
+                        }),
+                );
+            local
+        }
+        (_, _) => incoming,
+    };
+    if let Some(file_name) = file_name {
+        bookmark.book_path = book_dir.join(file_name).to_string_lossy().to_string();
+    }
+
+    let json_data = serde_json::to_string(&bookmark)
+        .map_err(|e| format!("Failed to serialize bookmark: {}", e))?;
+    fs::write(dest_path, json_data).map_err(|e| format!("Failed to write bookmark: {}", e))?;
+    report.restored_files += 1;
+    Ok(())
+}
+
+// 将临时文件移动到目标位置；目标已存在时只有 KeepIncoming 策略才覆盖
+fn restore_plain_file(
+    temp_path: &Path,
+    dest_path: &Path,
+    strategy: MergeStrategy,
+    report: &mut RestoreReport,
+) -> Result<(), String> {
+    if dest_path.exists() && strategy != MergeStrategy::KeepIncoming {
+        report.skipped_files += 1;
+        return Ok(());
+    }
+    if let Some(parent) = dest_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    fs::rename(temp_path, dest_path)
+        .or_else(|_| fs::copy(temp_path, dest_path).map(|_| ()))
+        .map_err(|e| format!("Failed to restore {}: {}", dest_path.display(), e))?;
+    report.restored_files += 1;
+    Ok(())
+}
+
+// 从备份压缩包恢复书库，合并到现有的 books/ 目录
+// 只有书签文件和配置文件会按策略合并，其他已存在的文件（epub、封面等）除非选择 KeepIncoming 否则保留本地版本
+// 本地和备份中都没有epub文件的书籍不恢复，记录在 skipped_books 中
+fn restore_library(
+    app_dir: &Path,
+    books_dir: &Path,
+    archive_path: &Path,
+    strategy: MergeStrategy,
+    job: &JobHandle,
+) -> Result<RestoreReport, String> {
+    let file =
+        File::open(archive_path).map_err(|e| format!("Failed to open backup archive: {}", e))?;
+    let mut archive =
+        ZipArchive::new(file).map_err(|e| format!("Failed to read backup archive: {}", e))?;
+    let manifest = read_manifest(&mut archive)?;
+    job.set_total(manifest.files.len());
+
+    // 每个恢复任务使用自己的临时目录，同时进行的恢复不会互相覆盖
+    let temp_root = app_dir.join("restore_tmp");
+    let temp_dir = temp_root.join(job.id());
+    // 本地已有epub文件的书籍
+    let existing_books: HashSet<String> = fs::read_dir(books_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| find_epub_in_dir(&entry.path()).is_some())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    let archived_books = books_in_archive(&manifest);
+    let mut report = RestoreReport::default();
+
+    for (index, entry) in manifest.files.iter().enumerate() {
+        if job.is_cancelled() {
+            break;
+        }
+        let Some(target) = resolve_target(app_dir, books_dir, &entry.path) else {
+            report
+                .errors
+                .push(format!("Skipped unexpected path in backup: {}", entry.path));
+            job.advance(Some(entry.path.clone()));
+            continue;
+        };
+
+        // 没有epub文件的书籍只恢复书签等文件没有意义
+        if let RestoreTarget::Bookmark { book_id, .. } | RestoreTarget::BookFile { book_id, .. } =
+            &target
+        {
+            if !existing_books.contains(book_id) {
+                if !archived_books.contains(book_id) {
+                    if !report.skipped_books.contains(book_id) {
+                        report.skipped_books.push(book_id.clone());
+                    }
+                    report.skipped_files += 1;
+                    job.advance(Some(entry.path.clone()));
+                    continue;
+                }
+                if !report.restored_books.contains(book_id) {
+                    report.restored_books.push(book_id.clone());
+                }
+            }
+        }
+
+        // 书籍ID是epub内容的哈希值，不一致的epub恢复后会被当作损坏的书籍
+        if let Some(id) = archived_epub_id(&entry.path) {
+            if !entry.sha256.eq_ignore_ascii_case(id) {
+                report.errors.push(format!(
+                    "{} does not match its book ID, skipped",
+                    entry.path
+                ));
+                job.advance(Some(entry.path.clone()));
+                continue;
+            }
+        }
+
+        let temp_path = temp_dir.join(index.to_string());
+        let result =
+            extract_verified(&mut archive, entry, &temp_path).and_then(|_| match &target {
+                RestoreTarget::Bookmark { book_id, path } => {
+                    restore_bookmark(book_id, &temp_path, path, strategy, &mut report)
+                }
+                RestoreTarget::BookFile { path, .. } => {
+                    restore_plain_file(&temp_path, path, strategy, &mut report)
+                }
+                RestoreTarget::Config { path } => {
+                    let _guard = lock_config_file(path);
+                    restore_plain_file(&temp_path, path, strategy, &mut report)
+                }
+            });
+        if let Err(e) = result {
+            report.errors.push(e);
+        }
+        let _ = fs::remove_file(&temp_path);
+        job.advance(Some(entry.path.clone()));
+    }
+    let _ = fs::remove_dir_all(&temp_dir);
+    // 其他恢复任务还在使用时目录不为空，不会被删除
+    let _ = fs::remove_dir(&temp_root);
+
+    Ok(report)
+}
+
+// 提交后台恢复任务，返回任务ID，完成后任务结果为恢复报告
+pub fn start_restore_job(
+    app_handle: &AppHandle,
+    archive_path: &str,
+    strategy: MergeStrategy,
+) -> Result<String, String> {
+    let app_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+    let books_dir = get_books_dir(app_handle)?;
+    let archive_path = PathBuf::from(archive_path);
+    let handle = app_handle.clone();
+
+    spawn_job(app_handle, JobKind::Restore, move |job| async move {
+        let report = tokio::task::spawn_blocking(move || {
+            restore_library(&app_dir, &books_dir, &archive_path, strategy, &job)
+        })
+        .await
+        .map_err(|e| format!("Restore task failed: {}", e))??;
+        // 恢复的书籍加入书库索引
+        reconcile_library_index(&handle).await?;
+        for id in &report.restored_books {
+            index_book_in_background(&handle, id);
+        }
+        serde_json::to_value(report).map_err(|e| format!("Failed to serialize report: {}", e))
+    })
+}
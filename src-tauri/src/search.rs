@@ -0,0 +1,636 @@
+use crate::file::find_epub_in_dir;
+use crate::jobs::spawn_job;
+use crate::library::get_book_dir;
+use crate::model::{BookSearchEvent, BookSearchQuery, JobKind, SearchMatch, TocItem};
+use crate::navigation::get_book_navigation;
+use crate::util::{is_cjk, strip_diacritic};
+use epub::doc::EpubDoc;
+use regex::RegexBuilder;
+use scraper::{ElementRef, Html, Node};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use tauri::{AppHandle, Emitter};
+use xml::reader::{EventReader, ParserConfig, XmlEvent};
+
+// 搜索结果事件，每搜索完一个章节发送一次
+pub const BOOK_SEARCH_EVENT: &str = "book-search-result";
+
+// 默认最多返回的结果数量
+const DEFAULT_MAX_RESULTS: usize = 1000;
+
+// 结果片段中匹配文本前后保留的字符数
+const SNIPPET_CONTEXT_CHARS: usize = 40;
+
+// 块级元素，前后插入空格，避免相邻段落的文字连在一起
+const BLOCK_ELEMENTS: [&str; 24] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "p",
+    "section",
+    "td",
+];
+
+// 文本中一个字符在文档中的位置
+#[derive(Clone, Copy)]
+struct CharPosition {
+    node: usize,   // Index into DocumentText::node_paths
+    offset: usize, // UTF-16 offset inside the text node, as used by CFI
+}
+
+// 从XHTML中提取的正文，空白已合并，保留每个字符在文档中的位置
+pub struct DocumentText {
+    pub chars: Vec<char>,
+    positions: Vec<CharPosition>,
+    node_paths: Vec<String>, // CFI path of each text node, e.g. "/4/2/1"
+}
+
+impl DocumentText {
+    // 追加一个字符，合并连续空白，中日文字符之间的换行不视为空格
+    fn push(&mut self, c: char, position: CharPosition) {
+        if c.is_whitespace() {
+            if self.chars.last().is_some_and(|last| *last != ' ') {
+                self.chars.push(' ');
+                self.positions.push(position);
+            }
+            return;
+        }
+        let len = self.chars.len();
+        if len >= 2 && self.chars[len - 1] == ' ' && is_cjk(self.chars[len - 2]) && is_cjk(c) {
+            self.chars.pop();
+            self.positions.pop();
+        }
+        self.chars.push(c);
+        self.positions.push(position);
+    }
+
+    // 在块级元素边界插入空格
+    fn push_break(&mut self) {
+        if let Some(&position) = self.positions.last() {
+            self.push(' ', position);
+        }
+    }
+
+    // 收集元素中的文字，path 为元素的CFI路径
+    fn collect(&mut self, element: ElementRef, path: &str) {
+        let mut element_index = 0;
+        for child in element.children() {
+            if let Some(child_element) = ElementRef::wrap(child) {
+                element_index += 1;
+                let name = child_element.value().name();
+                if matches!(name, "head" | "script" | "style") {
+                    continue;
+                }
+                let is_block = BLOCK_ELEMENTS.contains(&name);
+                if is_block {
+                    self.push_break();
+                }
+                self.collect(child_element, &format!("{}/{}", path, element_index * 2));
+                if is_block {
+                    self.push_break();
+                }
+            } else if let Node::Text(text) = child.value() {
+                // 文本节点的步骤为奇数：前面有 n 个元素时为 2n+1
+                let node = self.node_paths.len();
+                self.node_paths
+                    .push(format!("{}/{}", path, element_index * 2 + 1));
+                let mut offset = 0;
+                for c in text.chars() {
+                    self.push(c, CharPosition { node, offset });
+                    offset += c.len_utf16();
+                }
+            }
+        }
+    }
+
+    // 生成字符位置的CFI
+    pub fn cfi(&self, spine_step: &str, index: usize) -> String {
+        let position = self.positions[index.min(self.positions.len() - 1)];
+        format!(
+            "epubcfi({}!{}:{})",
+            spine_step, self.node_paths[position.node], position.offset
+        )
+    }
+}
+
+// 解析XHTML时一个尚未结束的元素
+struct OpenElement {
+    path: String,                      // CFI path of the element
+    children: usize,                   // Child elements seen so far
+    text_node: Option<(usize, usize)>, // Current text node and its UTF-16 length so far
+    skipped: bool,                     // Inside head/script/style
+    is_block: bool,
+}
+
+// 提取HTML文档的正文
+pub fn extract_document_text(html: &str) -> DocumentText {
+    let document = Html::parse_document(html);
+    let mut text = DocumentText {
+        chars: Vec::new(),
+        positions: Vec::new(),
+        node_paths: Vec::new(),
+    };
+    text.collect(document.root_element(), "");
+    text
+}
+
+// 按XML解析XHTML文档的正文，HTML解析器会把 <div/> 等自闭合元素当作开始标签，后面的元素被错误嵌套
+// 文档不是格式正确的XML时（例如使用了 &nbsp; 等未声明的实体）返回空
+pub fn extract_xhtml_text(xhtml: &str) -> Option<DocumentText> {
+    let reader = ParserConfig::new()
+        .whitespace_to_characters(true)
+        .cdata_to_characters(true)
+        .create_reader(xhtml.as_bytes());
+    let mut text = DocumentText {
+        chars: Vec::new(),
+        positions: Vec::new(),
+        node_paths: Vec::new(),
+    };
+    let mut open: Vec<OpenElement> = Vec::new();
+
+    for event in reader {
+        match event.ok()? {
+            XmlEvent::StartElement { name, .. } => {
+                let name = name.local_name.to_lowercase();
+                let element = match open.last_mut() {
+                    // 根元素 <html> 的路径为空，与 extract_document_text 一致
+                    None => OpenElement {
+                        path: String::new(),
+                        children: 0,
+                        text_node: None,
+                        skipped: false,
+                        is_block: false,
+                    },
+                    Some(parent) => {
+                        parent.children += 1;
+                        parent.text_node = None;
+                        OpenElement {
+                            path: format!("{}/{}", parent.path, parent.children * 2),
+                            children: 0,
+                            text_node: None,
+                            skipped: parent.skipped
+                                || matches!(name.as_str(), "head" | "script" | "style"),
+                            is_block: BLOCK_ELEMENTS.contains(&name.as_str()),
+                        }
+                    }
+                };
+                if element.is_block && !element.skipped {
+                    text.push_break();
+                }
+                open.push(element);
+            }
+            XmlEvent::EndElement { .. } => {
+                let element = open.pop()?;
+                if element.is_block && !element.skipped {
+                    text.push_break();
+                }
+                if let Some(parent) = open.last_mut() {
+                    parent.text_node = None;
+                }
+            }
+            XmlEvent::Characters(chars) => {
+                let Some(parent) = open.last_mut().filter(|parent| !parent.skipped) else {
+                    continue;
+                };
+                // 文本节点的步骤为奇数：前面有 n 个元素时为 2n+1
+                let (node, mut offset) = *parent.text_node.get_or_insert_with(|| {
+                    text.node_paths
+                        .push(format!("{}/{}", parent.path, parent.children * 2 + 1));
+                    (text.node_paths.len() - 1, 0)
+                });
+                for c in chars.chars() {
+                    text.push(c, CharPosition { node, offset });
+                    offset += c.len_utf16();
+                }
+                parent.text_node = Some((node, offset));
+            }
+            _ => {}
+        }
+    }
+    Some(text)
+}
+
+// package 元素中 <spine> 的CFI步骤，OPF中 metadata、manifest、spine 的顺序通常为前三个子元素，此时为 6
+pub fn spine_element_step(doc: &mut EpubDoc<BufReader<File>>) -> usize {
+    let root_file = doc.root_file.clone();
+    doc.get_resource_str_by_path(&root_file)
+        .map_or(6, |opf| spine_step_in_opf(&opf))
+}
+
+// 在OPF文档中查找 <spine> 是 package 的第几个子元素
+fn spine_step_in_opf(opf: &str) -> usize {
+    let mut depth = 0;
+    let mut children = 0;
+    for event in EventReader::new(opf.as_bytes()) {
+        match event {
+            Ok(XmlEvent::StartElement { name, .. }) => {
+                depth += 1;
+                if depth == 2 {
+                    children += 1;
+                    if name.local_name == "spine" {
+                        return children * 2;
+                    }
+                }
+            }
+            Ok(XmlEvent::EndElement { .. }) => depth -= 1,
+            Err(_) => break,
+            _ => {}
+        }
+    }
+    6
+}
+
+// 用于匹配的规范化文本，记录每个规范化字符对应的原始字符
+struct FoldedText {
+    text: String,
+    byte_offsets: Vec<usize>, // Byte offset of each folded char
+    sources: Vec<usize>,      // Index of the original char for each folded char
+}
+
+// 按搜索选项规范化文本：去掉变音符号、转小写
+fn fold_text(chars: &[char], fold_case: bool, fold_diacritics: bool) -> FoldedText {
+    let mut folded = FoldedText {
+        text: String::with_capacity(chars.len()),
+        byte_offsets: Vec::with_capacity(chars.len()),
+        sources: Vec::with_capacity(chars.len()),
+    };
+    for (index, &c) in chars.iter().enumerate() {
+        let c = if fold_diacritics {
+            match strip_diacritic(c) {
+                Some(c) => c,
+                None => continue,
+            }
+        } else {
+            c
+        };
+        let mut push = |c: char| {
+            folded.byte_offsets.push(folded.text.len());
+            folded.sources.push(index);
+            folded.text.push(c);
+        };
+        if fold_case {
+            c.to_lowercase().for_each(&mut push);
+        } else {
+            push(c);
+        }
+    }
+    folded
+}
+
+// 规范化搜索词，与正文使用相同的空白处理
+fn normalize_query(query: &str) -> Vec<char> {
+    let mut text = DocumentText {
+        chars: Vec::new(),
+        positions: Vec::new(),
+        node_paths: Vec::new(),
+    };
+    let position = CharPosition { node: 0, offset: 0 };
+    for c in query.trim().chars() {
+        text.push(c, position);
+    }
+    text.chars
+}
+
+// 搜索一个章节，返回匹配的原始字符范围
+fn find_matches(
+    text: &DocumentText,
+    query: &BookSearchQuery,
+    pattern: &Matcher,
+    limit: usize,
+) -> Vec<(usize, usize)> {
+    let folded = fold_text(&text.chars, !query.match_case, !query.match_diacritics);
+    let byte_ranges: Vec<(usize, usize)> = match pattern {
+        Matcher::Plain(needle) => folded
+            .text
+            .match_indices(needle.as_str())
+            .take(limit)
+            .map(|(start, m)| (start, start + m.len()))
+            .collect(),
+        Matcher::Regex(regex) => regex
+            .find_iter(&folded.text)
+            .filter(|m| !m.is_empty())
+            .take(limit)
+            .map(|m| (m.start(), m.end()))
+            .collect(),
+    };
+    byte_ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let first = folded
+                .byte_offsets
+                .partition_point(|&offset| offset < start);
+            let last = folded.byte_offsets.partition_point(|&offset| offset < end) - 1;
+            (folded.sources[first], folded.sources[last] + 1)
+        })
+        .collect()
+}
+
+// 编译后的搜索条件
+pub enum Matcher {
+    Plain(String),
+    Regex(regex::Regex),
+}
+
+pub fn build_matcher(query: &BookSearchQuery) -> Result<Matcher, String> {
+    if query.query.trim().is_empty() {
+        return Err("Search query is empty".to_string());
+    }
+    if query.regex {
+        // 正则表达式只去掉变音符号，大小写由正则引擎处理，避免改变 \W、\S 等转义
+        let chars: Vec<char> = query.query.chars().collect();
+        let pattern = fold_text(&chars, false, !query.match_diacritics).text;
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!query.match_case)
+            .build()
+            .map(Matcher::Regex)
+            .map_err(|e| format!("Invalid regular expression: {}", e))
+    } else {
+        let chars = normalize_query(&query.query);
+        Ok(Matcher::Plain(
+            fold_text(&chars, !query.match_case, !query.match_diacritics).text,
+        ))
+    }
+}
+
+// 获取章节标题：目录中第一个指向该章节的条目
+pub fn chapter_label(toc: &[TocItem], spine_index: usize) -> Option<String> {
+    toc.iter().find_map(|item| {
+        if item.spine_index == Some(spine_index) && !item.label.is_empty() {
+            Some(item.label.clone())
+        } else {
+            chapter_label(&item.subitems, spine_index)
+        }
+    })
+}
+
+// 阅读顺序中一个章节的正文
+pub struct ChapterText {
+    pub spine_index: usize,
+    pub href: String,       // Path of the chapter inside the epub
+    pub spine_step: String, // CFI step of the chapter in the spine, e.g. "/6/4[chap01]"
+    pub text: DocumentText,
+}
+
+// 读取阅读顺序中的一个章节，不是XHTML文档时返回空
+// spine_element 为 spine_element_step 的结果，每本书只需计算一次
+pub fn read_chapter_text(
+    doc: &mut EpubDoc<BufReader<File>>,
+    spine_element: usize,
+    spine_index: usize,
+) -> Option<ChapterText> {
+    let item = doc.spine.get(spine_index)?.clone();
+    let (path, mime) = doc.resources.get(&item.idref).cloned()?;
+    if !mime.contains("html") {
+        return None;
+    }
+    let html = doc.get_resource_str_by_path(&path)?;
+    // 每个 itemref 占偶数步骤
+    let spine_step = match &item.id {
+        Some(id) => format!("/{}/{}[{}]", spine_element, (spine_index + 1) * 2, id),
+        None => format!("/{}/{}", spine_element, (spine_index + 1) * 2),
+    };
+    let text = match mime.as_str() {
+        "application/xhtml+xml" => {
+            extract_xhtml_text(&html).unwrap_or_else(|| extract_document_text(&html))
+        }
+        _ => extract_document_text(&html),
+    };
+    Some(ChapterText {
+        spine_index,
+        href: path.to_string_lossy().replace('\\', "/"),
+        spine_step,
+        text,
+    })
+}
+
+// 在一个章节的正文中搜索，最多返回 limit 条结果
+pub fn search_chapter(
+    chapter: &ChapterText,
+    label: Option<String>,
+    query: &BookSearchQuery,
+    matcher: &Matcher,
+    limit: usize,
+) -> Vec<SearchMatch> {
+    let chars = &chapter.text.chars;
+    find_matches(&chapter.text, query, matcher, limit)
+        .into_iter()
+        .map(|(start, end)| {
+            let before_start = start.saturating_sub(SNIPPET_CONTEXT_CHARS);
+            let after_end = (end + SNIPPET_CONTEXT_CHARS).min(chars.len());
+            SearchMatch {
+                spine_index: chapter.spine_index,
+                href: chapter.href.clone(),
+                chapter: label.clone(),
+                cfi: chapter.text.cfi(&chapter.spine_step, start),
+                before: chars[before_start..start].iter().collect(),
+                matched: chars[start..end].iter().collect(),
+                after: chars[end..after_end].iter().collect(),
+            }
+        })
+        .collect()
+}
+
+// 逐章搜索epub，每章的结果通过事件发送，返回结果总数
+fn search_epub(
+    epub_path: &Path,
+    query: &BookSearchQuery,
+    toc: &[TocItem],
+    mut on_chapter: impl FnMut(usize, usize, Vec<SearchMatch>) -> bool,
+) -> Result<usize, String> {
+    let matcher = build_matcher(query)?;
+    let max_results = query.max_results.unwrap_or(DEFAULT_MAX_RESULTS);
+    let mut doc = EpubDoc::new(epub_path).map_err(|e| e.to_string())?;
+    let spine_len = doc.spine.len();
+    let spine_element = spine_element_step(&mut doc);
+    let mut total = 0;
+
+    for spine_index in 0..spine_len {
+        if total >= max_results {
+            break;
+        }
+        let matches = match read_chapter_text(&mut doc, spine_element, spine_index) {
+            Some(chapter) => search_chapter(
+                &chapter,
+                chapter_label(toc, spine_index),
+                query,
+                &matcher,
+                max_results - total,
+            ),
+            None => Vec::new(),
+        };
+        total += matches.len();
+        if !on_chapter(spine_index, spine_len, matches) {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+// 提交书内搜索任务，返回任务ID
+// 结果通过 book-search-result 事件分章节发送，任务结果为结果总数
+pub async fn start_book_search(
+    app_handle: &AppHandle,
+    id: &str,
+    query: BookSearchQuery,
+) -> Result<String, String> {
+    // 提前检查搜索条件，避免提交注定失败的任务
+    build_matcher(&query)?;
+    let book_dir = get_book_dir(app_handle, id)?;
+    let epub_path = find_epub_in_dir(&book_dir).ok_or_else(|| format!("Book not found: {}", id))?;
+    let toc = get_book_navigation(app_handle, id)
+        .await
+        .map(|navigation| navigation.toc)
+        .unwrap_or_default();
+    let handle = app_handle.clone();
+
+    spawn_job(app_handle, JobKind::Search, move |job| async move {
+        let total = tokio::task::spawn_blocking(move || {
+            search_epub(
+                &epub_path,
+                &query,
+                &toc,
+                |spine_index, spine_len, matches| {
+                    job.set_total(spine_len);
+                    if !matches.is_empty() {
+                        let _ = handle.emit(
+                            BOOK_SEARCH_EVENT,
+                            BookSearchEvent {
+                                job_id: job.id().to_string(),
+                                matches,
+                            },
+                        );
+                    }
+                    job.advance(Some(spine_index.to_string()));
+                    !job.is_cancelled()
+                },
+            )
+        })
+        .await
+        .map_err(|e| format!("Search task failed: {}", e))??;
+        Ok(serde_json::json!({ "total": total }))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> DocumentText {
+        let mut text = DocumentText {
+            chars: Vec::new(),
+            positions: Vec::new(),
+            node_paths: vec!["/4/2/1".to_string()],
+        };
+        let mut offset = 0;
+        for c in s.chars() {
+            text.push(c, CharPosition { node: 0, offset });
+            offset += c.len_utf16();
+        }
+        text
+    }
+
+    fn matches(haystack: &str, query: BookSearchQuery) -> Vec<(usize, usize)> {
+        let matcher = build_matcher(&query).unwrap();
+        find_matches(&text(haystack), &query, &matcher, 10)
+    }
+
+    fn plain(query: &str) -> BookSearchQuery {
+        BookSearchQuery {
+            query: query.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fold_text_maps_folded_chars_to_sources() {
+        let chars: Vec<char> = "İé".chars().collect();
+        let folded = fold_text(&chars, true, false);
+        assert_eq!(folded.text, "i\u{307}é");
+        assert_eq!(folded.sources, [0, 0, 1]);
+        assert_eq!(folded.byte_offsets, [0, 1, 3]);
+
+        let chars: Vec<char> = "Cafe\u{301}".chars().collect();
+        let folded = fold_text(&chars, false, true);
+        assert_eq!(folded.text, "Cafe");
+        assert_eq!(folded.sources, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn find_matches_ignores_case_and_diacritics_by_default() {
+        assert_eq!(matches("Café and CAFE", plain("cafe")), [(0, 4), (9, 13)]);
+        let query = BookSearchQuery {
+            match_diacritics: true,
+            ..plain("cafe")
+        };
+        assert_eq!(matches("Café and CAFE", query), [(9, 13)]);
+        let query = BookSearchQuery {
+            match_case: true,
+            ..plain("Cafe")
+        };
+        assert_eq!(matches("Café and CAFE", query), [(0, 4)]);
+    }
+
+    #[test]
+    fn find_matches_returns_char_ranges_after_multibyte_text() {
+        assert_eq!(matches("中文 ünïcode search", plain("search")), [(11, 17)]);
+        assert_eq!(matches("中文 ünïcode search", plain("unicode")), [(3, 10)]);
+        let query = BookSearchQuery {
+            regex: true,
+            ..plain(r"s\w+h")
+        };
+        assert_eq!(matches("中文 Search", query), [(3, 9)]);
+    }
+
+    #[test]
+    fn xhtml_text_records_cfi_paths_and_utf16_offsets() {
+        let xhtml = "<html><head><title>t</title></head><body>\
+                     <p>ab</p><p>😀x<b>y</b>z</p></body></html>";
+        let text = extract_xhtml_text(xhtml).unwrap();
+        assert_eq!(text.chars.iter().collect::<String>(), "ab 😀xyz ");
+        assert_eq!(text.cfi("/6/2", 0), "epubcfi(/6/2!/4/2/1:0)");
+        assert_eq!(text.cfi("/6/2", 4), "epubcfi(/6/2!/4/4/1:2)");
+        assert_eq!(text.cfi("/6/2", 5), "epubcfi(/6/2!/4/4/2/1:0)");
+        assert_eq!(text.cfi("/6/2", 6), "epubcfi(/6/2!/4/4/3:0)");
+    }
+
+    #[test]
+    fn xhtml_text_handles_self_closing_elements() {
+        let xhtml = "<html><body><div/><p>a</p></body></html>";
+        let text = extract_xhtml_text(xhtml).unwrap();
+        assert_eq!(text.cfi("/6/2", 0), "epubcfi(/6/2!/2/4/1:0)");
+        assert!(extract_xhtml_text("<html><body>&nbsp;</body></html>").is_none());
+    }
+
+    #[test]
+    fn spine_step_counts_package_children() {
+        let opf = r#"<?xml version="1.0"?>
+            <package xmlns="http://www.idpf.org/2007/opf">
+              <metadata><title>t</title></metadata>
+              <manifest><item id="a"/></manifest>
+              <spine><itemref idref="a"/></spine>
+            </package>"#;
+        assert_eq!(spine_step_in_opf(opf), 6);
+        let opf = "<package><metadata/><manifest/><bindings/><spine/></package>";
+        assert_eq!(spine_step_in_opf(opf), 8);
+        assert_eq!(spine_step_in_opf("not xml"), 6);
+    }
+}
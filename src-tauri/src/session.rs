@@ -0,0 +1,251 @@
+use crate::collection::generate_id;
+use crate::file::get_current_timestamp;
+use crate::library::{get_book_dir, get_books_dir};
+use crate::model::{
+    BookReadingTime, PeriodReadingTime, ReadingSession, ReadingStats, ReadingStatsQuery,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::AppHandle;
+
+// 阅读记录保存在书籍目录下
+pub const SESSIONS_FILE: &str = "sessions.json";
+
+// 阅读记录文件的读写锁，避免开始和更新阅读记录时互相覆盖
+static SESSION_LOCK: Mutex<()> = Mutex::new(());
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+// 读取书籍目录中的阅读记录
+pub fn read_sessions(hash_dir: &Path) -> Vec<ReadingSession> {
+    fs::read_to_string(hash_dir.join(SESSIONS_FILE))
+        .ok()
+        .and_then(|json_data| serde_json::from_str(&json_data).ok())
+        .unwrap_or_default()
+}
+
+// 写入阅读记录
+pub fn write_sessions(hash_dir: &Path, sessions: &[ReadingSession]) -> Result<(), String> {
+    let json_data = serde_json::to_string(sessions)
+        .map_err(|e| format!("Failed to serialize reading sessions: {}", e))?;
+    fs::write(hash_dir.join(SESSIONS_FILE), json_data)
+        .map_err(|e| format!("Failed to write reading sessions: {}", e))
+}
+
+// 开始一次阅读，打开书籍时调用
+// 阅读过程中前端定时调用 update_reading_session 延长结束时间，应用崩溃时最多丢失一个更新间隔
+pub async fn start_reading_session(
+    app_handle: &AppHandle,
+    book_id: &str,
+    cfi: String,
+) -> Result<ReadingSession, String> {
+    let book_dir = get_book_dir(app_handle, book_id)?;
+    if !book_dir.exists() {
+        return Err(format!("Book not found: {}", book_id));
+    }
+    let now = get_current_timestamp()?;
+    let session = ReadingSession {
+        id: generate_id(),
+        start: now,
+        end: now,
+        start_cfi: cfi.clone(),
+        end_cfi: cfi,
+        pages_turned: 0,
+    };
+
+    let _guard = SESSION_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut sessions = read_sessions(&book_dir);
+    sessions.push(session.clone());
+    write_sessions(&book_dir, &sessions)?;
+    Ok(session)
+}
+
+// 更新阅读记录的结束时间、位置和翻页数
+pub async fn update_reading_session(
+    app_handle: &AppHandle,
+    book_id: &str,
+    session_id: &str,
+    cfi: String,
+    pages_turned: u32,
+) -> Result<ReadingSession, String> {
+    let book_dir = get_book_dir(app_handle, book_id)?;
+    let now = get_current_timestamp()?;
+
+    let _guard = SESSION_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut sessions = read_sessions(&book_dir);
+    let session = sessions
+        .iter_mut()
+        .find(|s| s.id == session_id)
+        .ok_or_else(|| format!("Reading session not found: {}", session_id))?;
+    session.end = now.max(session.start);
+    session.end_cfi = cfi;
+    session.pages_turned = pages_turned;
+    let session = session.clone();
+    write_sessions(&book_dir, &sessions)?;
+    Ok(session)
+}
+
+// 获取一本书的所有阅读记录，按开始时间排序
+pub async fn load_reading_sessions(
+    app_handle: &AppHandle,
+    book_id: &str,
+) -> Result<Vec<ReadingSession>, String> {
+    let book_dir = get_book_dir(app_handle, book_id)?;
+    let mut sessions = read_sessions(&book_dir);
+    sessions.sort_by_key(|s| s.start);
+    Ok(sessions)
+}
+
+// 将自1970-01-01起的天数转换为 YYYY-MM-DD
+fn format_day(day: i64) -> String {
+    // Howard Hinnant 的 civil_from_days 算法
+    let z = day + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+// 本地时间的天数（自1970-01-01起）
+fn local_day(timestamp: i64, utc_offset: i64) -> i64 {
+    (timestamp + utc_offset).div_euclid(SECONDS_PER_DAY)
+}
+
+// 统计阅读时间：按书籍、按天、按周（周一开始），以及连续阅读天数
+// 跨越午夜的阅读记录按本地时间拆分到两天
+pub async fn get_reading_stats(
+    app_handle: &AppHandle,
+    query: ReadingStatsQuery,
+) -> Result<ReadingStats, String> {
+    let books_dir = get_books_dir(app_handle)?;
+    let now = get_current_timestamp()? as i64;
+    let utc_offset = query.utc_offset_minutes as i64 * 60;
+    let since = query.days.map(|days| {
+        let days = i64::try_from(days).unwrap_or(i64::MAX);
+        now.saturating_sub(days.saturating_mul(SECONDS_PER_DAY))
+    });
+
+    let mut stats = ReadingStats::default();
+    let mut per_day: BTreeMap<i64, u64> = BTreeMap::new();
+    let mut per_book: HashMap<String, BookReadingTime> = HashMap::new();
+
+    let hash_dirs = fs::read_dir(&books_dir)
+        .map(|entries| entries.filter_map(|entry| entry.ok()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    for entry in hash_dirs {
+        let book_id = entry.file_name().to_string_lossy().to_string();
+        for session in read_sessions(&entry.path()) {
+            let (mut start, end) = (session.start as i64, session.end as i64);
+            if let Some(since) = since {
+                if end <= since {
+                    continue;
+                }
+                start = start.max(since);
+            }
+            if end <= start {
+                continue;
+            }
+            let seconds = (end - start) as u64;
+
+            let book = per_book
+                .entry(book_id.clone())
+                .or_insert_with(|| BookReadingTime {
+                    book_id: book_id.clone(),
+                    ..Default::default()
+                });
+            book.seconds += seconds;
+            book.sessions += 1;
+            book.pages_turned += session.pages_turned as u64;
+            book.last_read = book.last_read.max(session.end);
+            stats.total_seconds += seconds;
+
+            // 按本地时间的天拆分
+            let mut day_start = start;
+            while day_start < end {
+                let day = local_day(day_start, utc_offset);
+                let next_day = (day + 1) * SECONDS_PER_DAY - utc_offset;
+                let day_end = end.min(next_day);
+                *per_day.entry(day).or_default() += (day_end - day_start) as u64;
+                day_start = day_end;
+            }
+        }
+    }
+
+    // 按周汇总，1970-01-01 是周四
+    let mut per_week: BTreeMap<i64, u64> = BTreeMap::new();
+    for (&day, &seconds) in &per_day {
+        let week_start = day - (day + 3).rem_euclid(7);
+        *per_week.entry(week_start).or_default() += seconds;
+    }
+
+    // 最长连续阅读天数
+    let mut previous: Option<i64> = None;
+    let mut streak = 0;
+    for &day in per_day.keys() {
+        streak = if previous == Some(day - 1) {
+            streak + 1
+        } else {
+            1
+        };
+        stats.longest_streak = stats.longest_streak.max(streak);
+        previous = Some(day);
+    }
+    // 当前连续阅读天数，今天还没有阅读时从昨天开始计算
+    let today = local_day(now, utc_offset);
+    let mut day = if per_day.contains_key(&today) {
+        today
+    } else {
+        today - 1
+    };
+    while per_day.contains_key(&day) {
+        stats.current_streak += 1;
+        day -= 1;
+    }
+
+    stats.per_book = per_book.into_values().collect();
+    stats
+        .per_book
+        .sort_by_key(|book| std::cmp::Reverse(book.seconds));
+    stats.per_day = per_day
+        .into_iter()
+        .map(|(day, seconds)| PeriodReadingTime {
+            start_date: format_day(day),
+            seconds,
+        })
+        .collect();
+    stats.per_week = per_week
+        .into_iter()
+        .map(|(day, seconds)| PeriodReadingTime {
+            start_date: format_day(day),
+            seconds,
+        })
+        .collect();
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_day_converts_days_since_epoch() {
+        assert_eq!(format_day(0), "1970-01-01");
+        assert_eq!(format_day(-1), "1969-12-31");
+        assert_eq!(format_day(19723), "2024-01-01");
+        assert_eq!(format_day(19782), "2024-02-29");
+    }
+
+    #[test]
+    fn local_day_applies_utc_offset() {
+        assert_eq!(local_day(82800, 0), 0);
+        assert_eq!(local_day(82800, 3600), 1);
+        assert_eq!(local_day(0, -3600), -1);
+    }
+}
@@ -0,0 +1,329 @@
+use crate::collection::generate_id;
+use crate::file::{get_current_timestamp, get_last_opened};
+use crate::library::{epub_file_from_record, get_books_dir, reconcile_library_index};
+use crate::model::{
+    EpubFile, LibraryRecord, RuleTextField, RuleTimeField, SmartRule, SmartShelf, SmartShelfStore,
+};
+use crate::progress::{get_progress_percentage, FINISHED_PERCENTAGE};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::AppHandle;
+use tauri::Manager;
+
+// 智能书架配置的读写锁
+pub(crate) static SMART_SHELF_LOCK: Mutex<()> = Mutex::new(());
+
+// 规则求值时需要的书籍信息
+struct RuleContext<'a> {
+    record: &'a LibraryRecord,
+    last_opened: Option<u64>,
+    progress: Option<f64>,
+    now: u64,
+}
+
+// 获取规则中文本字段的所有取值（作者、主题等可能有多个）
+fn text_values(context: &RuleContext, field: RuleTextField) -> Vec<String> {
+    let metadata = &context.record.metadata;
+    match field {
+        RuleTextField::Title => metadata.title.iter().cloned().collect(),
+        RuleTextField::Author => metadata.creators.iter().map(|c| c.name.clone()).collect(),
+        RuleTextField::Publisher => metadata.publisher.iter().cloned().collect(),
+        RuleTextField::Language => metadata.language.iter().cloned().collect(),
+        RuleTextField::Subject => metadata.subjects.clone(),
+        RuleTextField::Identifier => metadata
+            .identifiers
+            .iter()
+            .map(|i| i.value.clone())
+            .collect(),
+        RuleTextField::FileName => vec![context.record.file_name.clone()],
+    }
+}
+
+// 获取规则中时间字段的取值
+fn time_value(context: &RuleContext, field: RuleTimeField) -> Option<u64> {
+    match field {
+        RuleTimeField::LastOpened => context.last_opened,
+        RuleTimeField::AddedAt => Some(context.record.added_at),
+    }
+}
+
+// 判断书籍是否满足规则
+fn matches_rule(rule: &SmartRule, context: &RuleContext) -> bool {
+    match rule {
+        SmartRule::All { rules } => rules.iter().all(|r| matches_rule(r, context)),
+        SmartRule::Any { rules } => rules.iter().any(|r| matches_rule(r, context)),
+        SmartRule::Not { rule } => !matches_rule(rule, context),
+        SmartRule::Equals { field, value } => {
+            let value = value.trim().to_lowercase();
+            text_values(context, *field).iter().any(|v| {
+                let v = v.trim().to_lowercase();
+                // 语言代码只比较主标签时也视为相等，例如 zh 与 zh-CN
+                v == value
+                    || (*field == RuleTextField::Language
+                        && v.split(['-', '_']).next() == Some(value.as_str()))
+            })
+        }
+        SmartRule::Contains { field, value } => {
+            let value = value.trim().to_lowercase();
+            text_values(context, *field)
+                .iter()
+                .any(|v| v.to_lowercase().contains(&value))
+        }
+        SmartRule::WithinDays { field, days } => time_value(context, *field)
+            .map(|t| context.now.saturating_sub(t) <= days.saturating_mul(24 * 60 * 60))
+            .unwrap_or(false),
+        // 与 Not 组合表示“未读完”
+        SmartRule::Finished => context
+            .progress
+            .map(|p| p >= FINISHED_PERCENTAGE)
+            .unwrap_or(false),
+    }
+}
+
+// 智能书架配置文件路径 /com.rbook.app/config/smart_shelves.json
+fn get_smart_shelves_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Unable to get app data directory: {}", e))?;
+    Ok(app_dir.join("config").join("smart_shelves.json"))
+}
+
+// 读取所有智能书架，文件不存在时返回空列表
+fn read_smart_shelves(app_handle: &AppHandle) -> Result<SmartShelfStore, String> {
+    let shelves_path = get_smart_shelves_path(app_handle)?;
+    if !shelves_path.exists() {
+        return Ok(SmartShelfStore::default());
+    }
+    let json_data = fs::read_to_string(&shelves_path)
+        .map_err(|e| format!("Failed to read smart shelves file: {}", e))?;
+    serde_json::from_str(&json_data)
+        .map_err(|e| format!("Failed to deserialize smart shelves: {}", e))
+}
+
+// 保存所有智能书架
+fn write_smart_shelves(app_handle: &AppHandle, store: &SmartShelfStore) -> Result<(), String> {
+    let shelves_path = get_smart_shelves_path(app_handle)?;
+    if let Some(config_dir) = shelves_path.parent() {
+        if !config_dir.exists() {
+            fs::create_dir_all(config_dir)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+    }
+    let json_data = serde_json::to_string(store)
+        .map_err(|e| format!("Failed to serialize smart shelves: {}", e))?;
+    fs::write(&shelves_path, json_data)
+        .map_err(|e| format!("Failed to write smart shelves to file: {}", e))
+}
+
+// 获取所有智能书架
+pub async fn load_smart_shelves(app_handle: &AppHandle) -> Result<Vec<SmartShelf>, String> {
+    let _guard = SMART_SHELF_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(read_smart_shelves(app_handle)?.shelves)
+}
+
+// 保存智能书架，id为空时新建，否则更新已有的智能书架
+pub async fn save_smart_shelf(
+    app_handle: &AppHandle,
+    id: Option<String>,
+    name: &str,
+    rule: SmartRule,
+) -> Result<SmartShelf, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Smart shelf name must not be empty".to_string());
+    }
+    let now = get_current_timestamp()?;
+
+    let _guard = SMART_SHELF_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut store = read_smart_shelves(app_handle)?;
+    let shelf = match id {
+        Some(id) => {
+            let shelf = store
+                .shelves
+                .iter_mut()
+                .find(|s| s.id == id)
+                .ok_or_else(|| format!("Smart shelf not found: {}", id))?;
+            shelf.name = name.to_string();
+            shelf.rule = rule;
+            shelf.updated_at = now;
+            shelf.clone()
+        }
+        None => {
+            let shelf = SmartShelf {
+                id: generate_id(),
+                name: name.to_string(),
+                rule,
+                created_at: now,
+                updated_at: now,
+            };
+            store.shelves.push(shelf.clone());
+            shelf
+        }
+    };
+    write_smart_shelves(app_handle, &store)?;
+    Ok(shelf)
+}
+
+// 删除智能书架
+pub async fn delete_smart_shelf(app_handle: &AppHandle, id: &str) -> Result<(), String> {
+    let _guard = SMART_SHELF_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut store = read_smart_shelves(app_handle)?;
+    let count = store.shelves.len();
+    store.shelves.retain(|s| s.id != id);
+    if store.shelves.len() == count {
+        return Err(format!("Smart shelf not found: {}", id));
+    }
+    write_smart_shelves(app_handle, &store)
+}
+
+// 对书库中的所有书籍求值规则，返回满足条件的书籍
+pub async fn evaluate_smart_rule(
+    app_handle: &AppHandle,
+    rule: &SmartRule,
+) -> Result<Vec<EpubFile>, String> {
+    let books_dir = get_books_dir(app_handle)?;
+    let index = reconcile_library_index(app_handle).await?;
+    let now = get_current_timestamp()?;
+    Ok(index
+        .books
+        .iter()
+        .filter(|record| {
+            let context = RuleContext {
+                record,
+                last_opened: get_last_opened(&books_dir.join(&record.id)),
+                progress: get_progress_percentage(&books_dir.join(&record.id)),
+                now,
+            };
+            matches_rule(rule, &context)
+        })
+        .map(|record| epub_file_from_record(&books_dir, record))
+        .collect())
+}
+
+// 求值已保存的智能书架
+pub async fn evaluate_smart_shelf(
+    app_handle: &AppHandle,
+    id: &str,
+) -> Result<Vec<EpubFile>, String> {
+    let rule = {
+        let _guard = SMART_SHELF_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        read_smart_shelves(app_handle)?
+            .shelves
+            .into_iter()
+            .find(|s| s.id == id)
+            .map(|s| s.rule)
+            .ok_or_else(|| format!("Smart shelf not found: {}", id))?
+    };
+    evaluate_smart_rule(app_handle, &rule).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{BookMetadata, Creator};
+
+    const DAY: u64 = 24 * 60 * 60;
+    const NOW: u64 = 1000 * DAY;
+
+    fn record() -> LibraryRecord {
+        LibraryRecord {
+            id: "abc".to_string(),
+            file_name: "three_body.epub".to_string(),
+            cover_file: "cover.jpg".to_string(),
+            metadata: BookMetadata {
+                title: Some("三体".to_string()),
+                creators: vec![
+                    Creator {
+                        name: "刘慈欣".to_string(),
+                        role: Some("aut".to_string()),
+                    },
+                    Creator {
+                        name: "Liu Cixin".to_string(),
+                        role: None,
+                    },
+                ],
+                language: Some("zh-CN".to_string()),
+                ..Default::default()
+            },
+            added_at: NOW - 100 * DAY,
+            updated_at: NOW - 100 * DAY,
+            file_size: 1024,
+            cover_version: 1,
+            custom_cover_file: None,
+        }
+    }
+
+    fn rule(json: &str) -> SmartRule {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn context(record: &LibraryRecord, progress: Option<f64>) -> RuleContext<'_> {
+        RuleContext {
+            record,
+            last_opened: Some(NOW - 10 * DAY),
+            progress,
+            now: NOW,
+        }
+    }
+
+    #[test]
+    fn matches_language_not_finished_and_recent() {
+        let rule = rule(
+            r#"{"op":"all","rules":[
+                {"op":"equals","field":"language","value":"zh"},
+                {"op":"not","rule":{"op":"finished"}},
+                {"op":"within_days","field":"last_opened","days":30}]}"#,
+        );
+        let record = record();
+        assert!(matches_rule(&rule, &context(&record, Some(40.0))));
+        assert!(matches_rule(&rule, &context(&record, None)));
+        assert!(!matches_rule(&rule, &context(&record, Some(100.0))));
+    }
+
+    #[test]
+    fn text_rules_ignore_case_and_match_any_value() {
+        let record = record();
+        let context = context(&record, None);
+        assert!(matches_rule(
+            &rule(r#"{"op":"contains","field":"author","value":"liu"}"#),
+            &context
+        ));
+        assert!(matches_rule(
+            &rule(r#"{"op":"equals","field":"author","value":" 刘慈欣 "}"#),
+            &context
+        ));
+        assert!(!matches_rule(
+            &rule(r#"{"op":"equals","field":"language","value":"en"}"#),
+            &context
+        ));
+        assert!(matches_rule(
+            &rule(
+                r#"{"op":"any","rules":[
+                    {"op":"equals","field":"title","value":"球状闪电"},
+                    {"op":"contains","field":"file_name","value":"BODY"}]}"#
+            ),
+            &context
+        ));
+    }
+
+    #[test]
+    fn within_days_handles_missing_time_and_huge_windows() {
+        let record = record();
+        let mut context = context(&record, None);
+        assert!(!matches_rule(
+            &rule(r#"{"op":"within_days","field":"added_at","days":30}"#),
+            &context
+        ));
+        assert!(matches_rule(
+            &rule(r#"{"op":"within_days","field":"added_at","days":18446744073709551615}"#),
+            &context
+        ));
+        context.last_opened = None;
+        assert!(!matches_rule(
+            &rule(r#"{"op":"within_days","field":"last_opened","days":30}"#),
+            &context
+        ));
+    }
+}
@@ -0,0 +1,212 @@
+use crate::collection::remove_book_from_collections;
+use crate::duplicate::merge_book_state;
+use crate::file::{find_epub_in_dir, get_current_timestamp, lock_book_import};
+use crate::fulltext::{index_book_in_background, remove_book_from_fulltext_index};
+use crate::library::{
+    build_library_record, epub_file_from_record, get_book_dir, get_library_record,
+    remove_library_record, upsert_library_record,
+};
+use crate::model::{EpubFile, LibraryRecord, TrashedBook};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+use tauri::Manager;
+
+// 回收站中记录删除时间的文件
+const TRASHED_AT_FILE: &str = ".trashed";
+// 回收站中保存的索引记录，恢复时保留导入时间等信息
+const TRASHED_RECORD_FILE: &str = ".record.json";
+
+// 获取回收站目录 /com.rbook.app/trash
+fn get_trash_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+    Ok(app_dir.join("trash"))
+}
+
+// 回收站中的书籍目录 /com.rbook.app/trash/<id>
+pub fn get_trashed_book_dir(app_handle: &AppHandle, id: &str) -> Result<PathBuf, String> {
+    // 与书籍目录使用同样的ID校验
+    get_book_dir(app_handle, id)?;
+    Ok(get_trash_dir(app_handle)?.join(id))
+}
+
+// 书籍是否在回收站中
+pub fn is_book_trashed(app_handle: &AppHandle, id: &str) -> Result<bool, String> {
+    Ok(get_trash_dir(app_handle)?.join(id).exists())
+}
+
+// 将书籍移动到回收站
+// 整个哈希目录（epub、封面、书签、最后打开时间）一起移动
+// 书籍仍保留在书架中，从回收站恢复后重新出现
+pub async fn delete_book(app_handle: &AppHandle, id: &str) -> Result<(), String> {
+    let book_dir = get_book_dir(app_handle, id)?;
+    // 同一本书可能正在重新导入
+    let _import_guard = lock_book_import(id).await;
+    if !book_dir.exists() {
+        return Err(format!("Book not found: {}", id));
+    }
+
+    let trash_dir = get_trash_dir(app_handle)?;
+    if !trash_dir.exists() {
+        fs::create_dir_all(&trash_dir)
+            .map_err(|e| format!("Failed to create trash directory: {}", e))?;
+    }
+    let trashed_dir = trash_dir.join(id);
+    if trashed_dir.exists() {
+        // 回收站中已有同一本书（删除后又重新导入），先把旧副本的书签和阅读记录合并过来再替换
+        let epub_path = find_epub_in_dir(&book_dir)
+            .ok_or_else(|| format!("No epub file found for book: {}", id))?;
+        merge_book_state(&book_dir, &epub_path, std::slice::from_ref(&trashed_dir))?;
+        fs::remove_dir_all(&trashed_dir)
+            .map_err(|e| format!("Failed to remove old trashed book: {}", e))?;
+    }
+
+    let record = get_library_record(app_handle, id)?;
+    fs::rename(&book_dir, &trashed_dir)
+        .map_err(|e| format!("Failed to move book to trash: {}", e))?;
+
+    let now = get_current_timestamp()?;
+    fs::write(trashed_dir.join(TRASHED_AT_FILE), now.to_string())
+        .map_err(|e| format!("Failed to write trashed time: {}", e))?;
+    if let Some(record) = record {
+        let json_data = serde_json::to_string(&record)
+            .map_err(|e| format!("Failed to serialize library record: {}", e))?;
+        fs::write(trashed_dir.join(TRASHED_RECORD_FILE), json_data)
+            .map_err(|e| format!("Failed to write library record: {}", e))?;
+    }
+
+    if let Err(e) = remove_book_from_fulltext_index(app_handle, id) {
+        println!("Failed to remove {} from full-text index: {}", id, e);
+    }
+    remove_library_record(app_handle, id)
+}
+
+// 从回收站恢复书籍
+pub async fn restore_book(app_handle: &AppHandle, id: &str) -> Result<EpubFile, String> {
+    let book_dir = get_book_dir(app_handle, id)?;
+    let _import_guard = lock_book_import(id).await;
+    let trashed_dir = get_trash_dir(app_handle)?.join(id);
+    if !trashed_dir.exists() {
+        return Err(format!("Book not found in trash: {}", id));
+    }
+    if book_dir.exists() {
+        return Err(format!("Book already exists in library: {}", id));
+    }
+
+    let saved_record: Option<LibraryRecord> =
+        fs::read_to_string(trashed_dir.join(TRASHED_RECORD_FILE))
+            .ok()
+            .and_then(|json_data| serde_json::from_str(&json_data).ok());
+
+    if let Some(books_dir) = book_dir.parent() {
+        if !books_dir.exists() {
+            fs::create_dir_all(books_dir)
+                .map_err(|e| format!("Failed to create books directory: {}", e))?;
+        }
+    }
+    fs::rename(&trashed_dir, &book_dir)
+        .map_err(|e| format!("Failed to restore book from trash: {}", e))?;
+    let _ = fs::remove_file(book_dir.join(TRASHED_AT_FILE));
+    let _ = fs::remove_file(book_dir.join(TRASHED_RECORD_FILE));
+
+    let record = match saved_record {
+        Some(record) if book_dir.join(&record.file_name).exists() => record,
+        _ => {
+            let epub_path = find_epub_in_dir(&book_dir)
+                .ok_or_else(|| format!("No epub file found for book: {}", id))?;
+            build_library_record(&book_dir, &epub_path)?
+        }
+    };
+    upsert_library_record(app_handle, record.clone())?;
+    index_book_in_background(app_handle, id);
+
+    let books_dir = book_dir.parent().ok_or("Failed to get books directory")?;
+    Ok(epub_file_from_record(books_dir, &record))
+}
+
+// 读取回收站中的一本书
+fn read_trashed_book(trashed_dir: &Path) -> Option<TrashedBook> {
+    let id = trashed_dir.file_name()?.to_string_lossy().to_string();
+    let deleted_at = fs::read_to_string(trashed_dir.join(TRASHED_AT_FILE))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0);
+    let record: LibraryRecord = match fs::read_to_string(trashed_dir.join(TRASHED_RECORD_FILE))
+        .ok()
+        .and_then(|json_data| serde_json::from_str(&json_data).ok())
+    {
+        Some(record) => record,
+        None => {
+            let epub_path = find_epub_in_dir(trashed_dir)?;
+            build_library_record(trashed_dir, &epub_path).ok()?
+        }
+    };
+    let book = epub_file_from_record(trashed_dir.parent()?, &record);
+    (book.id == id).then_some(TrashedBook { book, deleted_at })
+}
+
+// 列出回收站中的所有书籍
+pub async fn load_trashed_books(app_handle: &AppHandle) -> Result<Vec<TrashedBook>, String> {
+    let trash_dir = get_trash_dir(app_handle)?;
+    if !trash_dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut books = Vec::new();
+    for entry in
+        fs::read_dir(&trash_dir).map_err(|e| format!("Failed to read trash directory: {}", e))?
+    {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        if !entry.path().is_dir() {
+            continue;
+        }
+        if let Some(book) = read_trashed_book(&entry.path()) {
+            books.push(book);
+        }
+    }
+    books.sort_by_key(|b| std::cmp::Reverse(b.deleted_at));
+    Ok(books)
+}
+
+// 清空回收站
+// retention_days 为空时删除所有书籍，否则只删除放入回收站超过指定天数的书籍
+// 返回被彻底删除的书籍ID，这些书籍同时从书架中移除
+pub async fn purge_trash(
+    app_handle: &AppHandle,
+    retention_days: Option<u64>,
+) -> Result<Vec<String>, String> {
+    let trash_dir = get_trash_dir(app_handle)?;
+    if !trash_dir.exists() {
+        return Ok(vec![]);
+    }
+    let now = get_current_timestamp()?;
+    let mut purged = Vec::new();
+    for entry in
+        fs::read_dir(&trash_dir).map_err(|e| format!("Failed to read trash directory: {}", e))?
+    {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        let trashed_dir = entry.path();
+        if !trashed_dir.is_dir() {
+            continue;
+        }
+        if let Some(days) = retention_days {
+            let deleted_at: u64 = fs::read_to_string(trashed_dir.join(TRASHED_AT_FILE))
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or(0);
+            if now.saturating_sub(deleted_at) < days.saturating_mul(24 * 60 * 60) {
+                continue;
+            }
+        }
+        fs::remove_dir_all(&trashed_dir)
+            .map_err(|e| format!("Failed to purge {}: {}", trashed_dir.display(), e))?;
+        let id = entry.file_name().to_string_lossy().to_string();
+        if let Err(e) = remove_book_from_collections(app_handle, &id) {
+            println!("Failed to remove {} from collections: {}", id, e);
+        }
+        purged.push(id);
+    }
+    Ok(purged)
+}
@@ -0,0 +1,375 @@
+use crate::epub_builder::{
+    chapter_file_name, escape_xml, guess_language, import_generated_book, GeneratedBook,
+    GeneratedChapter,
+};
+use crate::model::{EpubFile, TocItem, TxtImportSettings};
+use encoding_rs::{Encoding, GB18030, UTF_16BE, UTF_16LE, UTF_8};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use tauri::AppHandle;
+use tauri::Manager;
+
+// 没有识别到章节标题时，按该长度（字符数）在段落处切分，避免单个章节过大
+const MAX_CHAPTER_CHARS: usize = 20_000;
+
+// 用于判断编码的采样长度
+const ENCODING_SAMPLE_BYTES: usize = 64 * 1024;
+
+// 判断文件是否为纯文本文件
+pub fn is_text_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case("txt"))
+        .unwrap_or(false)
+}
+
+// TXT 导入配置文件路径 /com.rbook.app/config/txt_import.json
+fn get_txt_import_config_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Unable to get app data directory: {}", e))?;
+    Ok(app_dir.join("config").join("txt_import.json"))
+}
+
+// 读取 TXT 导入配置，不存在时使用默认的章节标题规则
+pub async fn load_txt_import_settings(app_handle: &AppHandle) -> Result<TxtImportSettings, String> {
+    let config_path = get_txt_import_config_path(app_handle)?;
+    if !config_path.exists() {
+        return Ok(TxtImportSettings::default());
+    }
+    let json_data = fs::read_to_string(&config_path)
+        .map_err(|e| format!("Failed to read txt import settings: {}", e))?;
+    serde_json::from_str(&json_data)
+        .map_err(|e| format!("Failed to deserialize txt import settings: {}", e))
+}
+
+// 保存 TXT 导入配置，章节标题规则必须是合法的正则表达式
+pub async fn save_txt_import_settings(
+    app_handle: &AppHandle,
+    settings: &TxtImportSettings,
+) -> Result<(), String> {
+    compile_patterns(&settings.chapter_patterns)?;
+    let config_path = get_txt_import_config_path(app_handle)?;
+    if let Some(config_dir) = config_path.parent() {
+        if !config_dir.exists() {
+            fs::create_dir_all(config_dir)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+    }
+    let json_data = serde_json::to_string(settings)
+        .map_err(|e| format!("Failed to serialize txt import settings: {}", e))?;
+    fs::write(&config_path, json_data)
+        .map_err(|e| format!("Failed to write txt import settings: {}", e))
+}
+
+fn compile_patterns(patterns: &[String]) -> Result<Vec<Regex>, String> {
+    patterns
+        .iter()
+        .filter(|pattern| !pattern.trim().is_empty())
+        .map(|pattern| {
+            Regex::new(pattern).map_err(|e| format!("Invalid chapter pattern {}: {}", pattern, e))
+        })
+        .collect()
+}
+
+// 识别文本编码：优先按 BOM 判断
+// 正常的文本中不会出现 0 字节，出现时按没有 BOM 的 UTF-16 处理，0 字节在奇数位为小端
+// 其次是合法的 UTF-8，其余情况按 GB18030 解码，兼容 GBK 和 GB2312
+fn detect_encoding(data: &[u8]) -> (&'static Encoding, usize) {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(data) {
+        return (encoding, bom_length);
+    }
+    let sample = &data[..data.len().min(ENCODING_SAMPLE_BYTES)];
+
+    // 只含 ASCII 的 UTF-16 文本也是合法的 UTF-8，必须先检查 0 字节
+    let even_zeros = sample.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_zeros = sample
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|b| **b == 0)
+        .count();
+    if even_zeros + odd_zeros > 0 {
+        return (
+            if odd_zeros >= even_zeros {
+                UTF_16LE
+            } else {
+                UTF_16BE
+            },
+            0,
+        );
+    }
+
+    match std::str::from_utf8(sample) {
+        Ok(_) => return (UTF_8, 0),
+        // 采样在多字节字符中间截断
+        Err(e) if e.error_len().is_none() && sample.len() < data.len() => return (UTF_8, 0),
+        Err(_) => {}
+    }
+
+    (GB18030, 0)
+}
+
+// 将文件内容转换为 UTF-8 文本
+pub fn decode_text(data: &[u8]) -> String {
+    let (encoding, bom_length) = detect_encoding(data);
+    encoding
+        .decode_without_bom_handling(&data[bom_length..])
+        .0
+        .into_owned()
+}
+
+// 由正文拆出的章节
+struct TxtChapter {
+    title: Option<String>, // None for text before the first heading
+    paragraphs: Vec<String>,
+}
+
+// 按章节标题规则拆分章节，每一行去掉首尾空白后为一个段落
+fn split_chapters(text: &str, patterns: &[Regex]) -> Vec<TxtChapter> {
+    let mut chapters = vec![TxtChapter {
+        title: None,
+        paragraphs: Vec::new(),
+    }];
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if patterns.iter().any(|pattern| pattern.is_match(line)) {
+            chapters.push(TxtChapter {
+                title: Some(line.to_string()),
+                paragraphs: Vec::new(),
+            });
+        } else if let Some(chapter) = chapters.last_mut() {
+            chapter.paragraphs.push(line.to_string());
+        }
+    }
+    if chapters[0].paragraphs.is_empty() {
+        chapters.remove(0);
+    }
+    chapters
+}
+
+// 将没有章节标题的长文本按长度切分
+fn split_by_length(paragraphs: Vec<String>) -> Vec<Vec<String>> {
+    let mut parts: Vec<Vec<String>> = Vec::new();
+    let mut length = 0;
+    for paragraph in paragraphs {
+        if parts.is_empty() || length >= MAX_CHAPTER_CHARS {
+            parts.push(Vec::new());
+            length = 0;
+        }
+        length += paragraph.chars().count();
+        if let Some(part) = parts.last_mut() {
+            part.push(paragraph);
+        }
+    }
+    parts
+}
+
+// 文件名或正文开头的“作者：某某”
+static AUTHOR_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"作者[:：]\s*(.+)$").unwrap());
+
+// 从文件名和开头几行中解析书名和作者
+// 常见的文件名格式为《书名》作者：某某.txt，正文开头常有“作者：某某”一行
+fn parse_title_author(stem: &str, preamble: &[String]) -> (String, Option<String>) {
+    let (title_part, mut author) = match AUTHOR_PATTERN.captures(stem) {
+        Some(captures) => (
+            &stem[..captures.get(0).unwrap().start()],
+            Some(captures[1].trim().to_string()),
+        ),
+        None => (stem, None),
+    };
+    let title = match (title_part.find('《'), title_part.find('》')) {
+        (Some(start), Some(end)) if start < end => &title_part[start + '《'.len_utf8()..end],
+        _ => title_part,
+    };
+    let title = title.trim();
+    if author.is_none() {
+        author = preamble
+            .iter()
+            .take(10)
+            .find_map(|line| AUTHOR_PATTERN.captures(line))
+            .map(|captures| captures[1].trim().to_string());
+    }
+    let title = if title.is_empty() { stem.trim() } else { title };
+    (
+        title.to_string(),
+        author.filter(|author| !author.is_empty()),
+    )
+}
+
+fn chapter_body(title: &str, paragraphs: &[String]) -> String {
+    let mut body = format!("<h2>{}</h2>\n", escape_xml(title));
+    for paragraph in paragraphs {
+        body.push_str(&format!(
+            "<p class=\"text\">{}</p>\n",
+            escape_xml(paragraph)
+        ));
+    }
+    body
+}
+
+// 将 TXT 文件转换为书籍
+fn convert_txt_file(path: &Path, settings: &TxtImportSettings) -> Result<GeneratedBook, String> {
+    let patterns = compile_patterns(&settings.chapter_patterns)?;
+    let data = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let text = decode_text(&data);
+    let mut chapters = split_chapters(&text, &patterns);
+    if chapters.is_empty() {
+        return Err(format!("No text found in {}", path.display()));
+    }
+
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let preamble = match chapters.first() {
+        Some(chapter) if chapter.title.is_none() => chapter.paragraphs.as_slice(),
+        _ => &[],
+    };
+    let (title, author) = parse_title_author(&stem, preamble);
+
+    // 没有识别到任何章节标题时按长度切分
+    if chapters.len() == 1 && chapters[0].title.is_none() {
+        let paragraphs = std::mem::take(&mut chapters[0].paragraphs);
+        chapters = split_by_length(paragraphs)
+            .into_iter()
+            .enumerate()
+            .map(|(index, paragraphs)| TxtChapter {
+                title: Some(format!("{} ({})", title, index + 1)),
+                paragraphs,
+            })
+            .collect();
+    }
+
+    let mut generated = Vec::with_capacity(chapters.len());
+    let mut toc = Vec::with_capacity(chapters.len());
+    for (index, chapter) in chapters.into_iter().enumerate() {
+        let chapter_title = chapter.title.unwrap_or_else(|| title.clone());
+        toc.push(TocItem {
+            label: chapter_title.clone(),
+            href: chapter_file_name(index),
+            level: 0,
+            spine_index: Some(index),
+            subitems: Vec::new(),
+        });
+        generated.push(GeneratedChapter {
+            body: chapter_body(&chapter_title, &chapter.paragraphs),
+            title: chapter_title,
+        });
+    }
+
+    Ok(GeneratedBook {
+        language: guess_language(&text).to_string(),
+        title,
+        author,
+        chapters: generated,
+        toc,
+        resources: Vec::new(),
+    })
+}
+
+// 将 TXT 文件转换为 epub 并导入书库
+// 返回epub文件信息以及是否为新导入
+pub async fn import_txt(
+    app_handle: &AppHandle,
+    origin_path: &Path,
+) -> Result<(EpubFile, bool), String> {
+    let settings = load_txt_import_settings(app_handle).await?;
+    let path = origin_path.to_path_buf();
+    let book = tokio::task::spawn_blocking(move || convert_txt_file(&path, &settings))
+        .await
+        .map_err(|e| format!("Convert task failed: {}", e))??;
+    let file_stem = origin_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    import_generated_book(app_handle, book, &file_stem).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_patterns() -> Vec<Regex> {
+        compile_patterns(&TxtImportSettings::default().chapter_patterns).unwrap()
+    }
+
+    #[test]
+    fn detect_encoding_uses_bom() {
+        assert_eq!(detect_encoding(b"\xEF\xBB\xBFabc"), (UTF_8, 3));
+        assert_eq!(detect_encoding(b"\xFF\xFEa\x00"), (UTF_16LE, 2));
+        assert_eq!(detect_encoding(b"\xFE\xFF\x00a"), (UTF_16BE, 2));
+    }
+
+    #[test]
+    fn detect_encoding_recognizes_utf16_without_bom() {
+        // 只含 ASCII 的 UTF-16 同时也是合法的 UTF-8
+        assert_eq!(detect_encoding(b"a\x00b\x00c\x00"), (UTF_16LE, 0));
+        assert_eq!(detect_encoding(b"\x00a\x00b\x00c"), (UTF_16BE, 0));
+    }
+
+    #[test]
+    fn detect_encoding_accepts_utf8_cut_at_sample_end() {
+        let text = "中".repeat(ENCODING_SAMPLE_BYTES / 3 + 10);
+        assert_ne!(ENCODING_SAMPLE_BYTES % 3, 0);
+        assert_eq!(detect_encoding(text.as_bytes()), (UTF_8, 0));
+    }
+
+    #[test]
+    fn detect_encoding_falls_back_to_gb18030() {
+        let (data, _, _) = GB18030.encode("第一章 开始");
+        assert_eq!(detect_encoding(&data), (GB18030, 0));
+        assert_eq!(decode_text(&data), "第一章 开始");
+    }
+
+    #[test]
+    fn split_chapters_keeps_preamble_and_headings() {
+        let text = "简介\n\n第一章 开始\n  第一段  \n第二段\n第二章 结束\n尾\n";
+        let chapters = split_chapters(text, &default_patterns());
+        let titles: Vec<_> = chapters.iter().map(|c| c.title.as_deref()).collect();
+        assert_eq!(titles, [None, Some("第一章 开始"), Some("第二章 结束")]);
+        assert_eq!(chapters[0].paragraphs, ["简介"]);
+        assert_eq!(chapters[1].paragraphs, ["第一段", "第二段"]);
+        assert_eq!(chapters[2].paragraphs, ["尾"]);
+    }
+
+    #[test]
+    fn split_chapters_drops_empty_preamble() {
+        let text = "Chapter 1 Start\nOne\nCHAPTER IV\nFour\n";
+        let chapters = split_chapters(text, &default_patterns());
+        let titles: Vec<_> = chapters.iter().map(|c| c.title.as_deref()).collect();
+        assert_eq!(titles, [Some("Chapter 1 Start"), Some("CHAPTER IV")]);
+    }
+
+    #[test]
+    fn parse_title_author_from_file_name() {
+        assert_eq!(
+            parse_title_author("《三体》作者：刘慈欣", &[]),
+            ("三体".to_string(), Some("刘慈欣".to_string()))
+        );
+        assert_eq!(
+            parse_title_author(" 三体 ", &[]),
+            ("三体".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn parse_title_author_from_preamble() {
+        let preamble = vec!["三体".to_string(), "作者: 刘慈欣 ".to_string()];
+        assert_eq!(
+            parse_title_author("三体", &preamble),
+            ("三体".to_string(), Some("刘慈欣".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_title_author_keeps_stem_when_title_is_empty() {
+        assert_eq!(parse_title_author("《》", &[]), ("《》".to_string(), None));
+    }
+}
@@ -0,0 +1,356 @@
+use crate::file::import_file;
+use crate::import::scan_epub_files;
+use crate::model::{EpubFile, LibraryChangedEvent, WatchFolderConfig, WatchScanCache, WatchedFile};
+use notify::event::{EventKind, ModifyKind};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+// 书库发生变化时发送给所有窗口的事件
+pub const LIBRARY_CHANGED_EVENT: &str = "library-changed";
+
+// 文件大小在该时间内没有变化才认为同步完成
+const SETTLE_DELAY: Duration = Duration::from_secs(2);
+
+// 监视文件夹配置的读写锁
+static WATCH_CONFIG_LOCK: Mutex<()> = Mutex::new(());
+// 已处理文件记录的读写锁，多个文件夹同时扫描时避免互相覆盖
+static WATCH_SCAN_LOCK: Mutex<()> = Mutex::new(());
+
+// 监视文件夹的运行状态
+#[derive(Default)]
+pub struct WatchState {
+    watcher: Mutex<Option<RecommendedWatcher>>,
+    pending: Mutex<HashSet<PathBuf>>, // 正在等待导入的文件，避免重复导入
+}
+
+// 监视文件夹配置文件路径 /com.rbook.app/config/watch_folders.json
+fn get_watch_config_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let app_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Unable to get app data directory: {}", e))?;
+    Ok(app_dir.join("config").join("watch_folders.json"))
+}
+
+// 读取监视文件夹配置
+fn read_watch_config(app_handle: &AppHandle) -> Result<WatchFolderConfig, String> {
+    let config_path = get_watch_config_path(app_handle)?;
+    if !config_path.exists() {
+        return Ok(WatchFolderConfig::default());
+    }
+    let json_data = fs::read_to_string(&config_path)
+        .map_err(|e| format!("Failed to read watch folder config: {}", e))?;
+    serde_json::from_str(&json_data)
+        .map_err(|e| format!("Failed to deserialize watch folder config: {}", e))
+}
+
+// 保存监视文件夹配置
+fn write_watch_config(app_handle: &AppHandle, config: &WatchFolderConfig) -> Result<(), String> {
+    let config_path = get_watch_config_path(app_handle)?;
+    if let Some(config_dir) = config_path.parent() {
+        if !config_dir.exists() {
+            fs::create_dir_all(config_dir)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+    }
+    let json_data = serde_json::to_string(config)
+        .map_err(|e| format!("Failed to serialize watch folder config: {}", e))?;
+    fs::write(&config_path, json_data)
+        .map_err(|e| format!("Failed to write watch folder config: {}", e))
+}
+
+// 已处理文件记录的路径 /com.rbook.app/config/watch_scan.json
+fn get_watch_scan_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    Ok(get_watch_config_path(app_handle)?.with_file_name("watch_scan.json"))
+}
+
+// 读取已处理文件记录，文件损坏时重新扫描所有文件
+fn read_watch_scan(app_handle: &AppHandle) -> Result<WatchScanCache, String> {
+    let scan_path = get_watch_scan_path(app_handle)?;
+    Ok(fs::read_to_string(&scan_path)
+        .ok()
+        .and_then(|json_data| serde_json::from_str(&json_data).ok())
+        .unwrap_or_default())
+}
+
+// 保存已处理文件记录
+fn write_watch_scan(app_handle: &AppHandle, cache: &WatchScanCache) -> Result<(), String> {
+    let scan_path = get_watch_scan_path(app_handle)?;
+    if let Some(config_dir) = scan_path.parent() {
+        if !config_dir.exists() {
+            fs::create_dir_all(config_dir)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+    }
+    let json_data = serde_json::to_string(cache)
+        .map_err(|e| format!("Failed to serialize watch scan cache: {}", e))?;
+    fs::write(&scan_path, json_data).map_err(|e| format!("Failed to write watch scan cache: {}", e))
+}
+
+// 更新已处理文件记录：folder 下不在 files 中的记录视为已删除
+fn update_watch_scan(
+    app_handle: &AppHandle,
+    folder: Option<&Path>,
+    files: Vec<(PathBuf, WatchedFile)>,
+) -> Result<(), String> {
+    let _guard = WATCH_SCAN_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut cache = read_watch_scan(app_handle)?;
+    if let Some(folder) = folder {
+        cache
+            .files
+            .retain(|path, _| !Path::new(path).starts_with(folder));
+    }
+    for (path, state) in files {
+        cache
+            .files
+            .insert(path.to_string_lossy().to_string(), state);
+    }
+    write_watch_scan(app_handle, &cache)
+}
+
+// 读取文件的大小和修改时间
+fn watched_file_state(path: &Path) -> Option<WatchedFile> {
+    let meta = fs::metadata(path).ok()?;
+    let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(WatchedFile {
+        size: meta.len(),
+        modified: modified.as_secs(),
+    })
+}
+
+// 导入监视文件夹中的文件，返回新导入的书籍
+// 已在回收站中的书籍说明用户删除过，不再自动导入
+async fn import_watched_file(
+    app_handle: &AppHandle,
+    path: &Path,
+) -> Result<Option<EpubFile>, String> {
+    match import_file(app_handle, path, true).await? {
+        Some((book, true)) => Ok(Some(book)),
+        _ => Ok(None),
+    }
+}
+
+// 判断是否为epub文件
+fn is_epub_path(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case("epub"))
+        .unwrap_or(false)
+}
+
+// 等待文件写入完成后导入，导入成功则通知前端刷新书库
+async fn import_when_settled(app_handle: AppHandle, path: PathBuf) {
+    let mut last_size = None;
+    loop {
+        tokio::time::sleep(SETTLE_DELAY).await;
+        let size = match fs::metadata(&path) {
+            Ok(meta) => meta.len(),
+            Err(_) => break, // 文件已被移走
+        };
+        if last_size == Some(size) {
+            match import_watched_file(&app_handle, &path).await {
+                Ok(imported) => {
+                    if let Some(book) = imported {
+                        let _ = app_handle.emit(
+                            LIBRARY_CHANGED_EVENT,
+                            LibraryChangedEvent {
+                                imported: vec![book],
+                            },
+                        );
+                    }
+                    if let Some(state) = watched_file_state(&path) {
+                        if let Err(e) =
+                            update_watch_scan(&app_handle, None, vec![(path.clone(), state)])
+                        {
+                            println!("Failed to update watch scan cache: {}", e);
+                        }
+                    }
+                }
+                Err(e) => println!("Failed to import watched file {}: {}", path.display(), e),
+            }
+            break;
+        }
+        last_size = Some(size);
+    }
+
+    let state = app_handle.state::<WatchState>();
+    state
+        .pending
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&path);
+}
+
+// 处理文件系统事件，新出现的epub文件进入导入队列
+fn handle_watch_event(app_handle: &AppHandle, event: Event) {
+    let is_new_file = matches!(
+        event.kind,
+        EventKind::Create(_)
+            | EventKind::Modify(ModifyKind::Name(_))
+            | EventKind::Modify(ModifyKind::Data(_))
+    );
+    if !is_new_file {
+        return;
+    }
+
+    let state = app_handle.state::<WatchState>();
+    for path in event.paths.into_iter().filter(|p| is_epub_path(p)) {
+        let newly_pending = state
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(path.clone());
+        if newly_pending {
+            tauri::async_runtime::spawn(import_when_settled(app_handle.clone(), path));
+        }
+    }
+}
+
+// 导入文件夹中已存在的epub文件，有新书时通知前端
+// 上次扫描后大小和修改时间都没有变化的文件直接跳过
+async fn import_existing(app_handle: AppHandle, folder: String) {
+    let folder = PathBuf::from(folder);
+    let scan_folder = folder.clone();
+    let files =
+        match tauri::async_runtime::spawn_blocking(move || scan_epub_files(&scan_folder)).await {
+            Ok((files, failures)) => {
+                for failure in failures {
+                    println!(
+                        "Failed to scan watch folder: {}",
+                        failure.error.unwrap_or_default()
+                    );
+                }
+                files
+            }
+            Err(e) => {
+                println!("Failed to scan watch folder {}: {}", folder.display(), e);
+                return;
+            }
+        };
+    let cache = match read_watch_scan(&app_handle) {
+        Ok(cache) => cache,
+        Err(e) => {
+            println!("Failed to read watch scan cache: {}", e);
+            WatchScanCache::default()
+        }
+    };
+
+    let mut imported = Vec::new();
+    let mut scanned = Vec::new();
+    for path in files {
+        let Some(state) = watched_file_state(&path) else {
+            continue;
+        };
+        let unchanged = cache
+            .files
+            .get(path.to_string_lossy().as_ref())
+            .is_some_and(|cached| *cached == state);
+        if !unchanged {
+            match import_watched_file(&app_handle, &path).await {
+                Ok(book) => imported.extend(book),
+                Err(e) => {
+                    // 导入失败的文件不记录，下次启动时重试
+                    println!("Failed to import watched file {}: {}", path.display(), e);
+                    continue;
+                }
+            }
+        }
+        scanned.push((path, state));
+    }
+
+    if let Err(e) = update_watch_scan(&app_handle, Some(&folder), scanned) {
+        println!("Failed to update watch scan cache: {}", e);
+    }
+    if !imported.is_empty() {
+        let _ = app_handle.emit(LIBRARY_CHANGED_EVENT, LibraryChangedEvent { imported });
+    }
+}
+
+// 启动文件系统监视，监视配置中的所有文件夹
+pub fn init_watch_folders(app_handle: &AppHandle) -> Result<(), String> {
+    let handle = app_handle.clone();
+    let watcher = notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+        Ok(event) => handle_watch_event(&handle, event),
+        Err(e) => println!("Watch error: {}", e),
+    })
+    .map_err(|e| format!("Failed to create file watcher: {}", e))?;
+
+    let state = app_handle.state::<WatchState>();
+    let mut guard = state.watcher.lock().unwrap_or_else(|e| e.into_inner());
+    let watcher = guard.insert(watcher);
+
+    for folder in read_watch_config(app_handle)?.folders {
+        if let Err(e) = watcher.watch(Path::new(&folder), RecursiveMode::Recursive) {
+            println!("Failed to watch {}: {}", folder, e);
+            continue;
+        }
+        // 应用未运行期间放入的文件
+        tauri::async_runtime::spawn(import_existing(app_handle.clone(), folder));
+    }
+    Ok(())
+}
+
+// 获取所有监视文件夹
+pub async fn load_watch_folders(app_handle: &AppHandle) -> Result<Vec<String>, String> {
+    Ok(read_watch_config(app_handle)?.folders)
+}
+
+// 添加监视文件夹，并导入其中已有的epub文件
+pub async fn add_watch_folder(
+    app_handle: &AppHandle,
+    state: State<'_, WatchState>,
+    folder: &str,
+) -> Result<Vec<String>, String> {
+    let path = Path::new(folder);
+    if !path.is_dir() {
+        return Err(format!("Not a directory: {}", folder));
+    }
+    let _guard = WATCH_CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut config = read_watch_config(app_handle)?;
+    if config.folders.iter().any(|f| f == folder) {
+        return Ok(config.folders);
+    }
+
+    if let Some(watcher) = state
+        .watcher
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_mut()
+    {
+        watcher
+            .watch(path, RecursiveMode::Recursive)
+            .map_err(|e| format!("Failed to watch {}: {}", folder, e))?;
+    }
+
+    config.folders.push(folder.to_string());
+    write_watch_config(app_handle, &config)?;
+    tauri::async_runtime::spawn(import_existing(app_handle.clone(), folder.to_string()));
+    Ok(config.folders)
+}
+
+// 移除监视文件夹，已导入的书籍不受影响
+pub async fn remove_watch_folder(
+    app_handle: &AppHandle,
+    state: State<'_, WatchState>,
+    folder: &str,
+) -> Result<Vec<String>, String> {
+    let _guard = WATCH_CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut config = read_watch_config(app_handle)?;
+    config.folders.retain(|f| f != folder);
+    write_watch_config(app_handle, &config)?;
+
+    if let Some(watcher) = state
+        .watcher
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_mut()
+    {
+        // 文件夹可能已被删除，忽略取消监视的错误
+        let _ = watcher.unwatch(Path::new(folder));
+    }
+    Ok(config.folders)
+}
@@ -6,9 +6,12 @@ export interface BookMetadata {
 }
 
 export interface MenuItem {
+  id?: string; // book id (sha256 of the .epub file)
   cover: string; // path to cover image
+  thumbnail: string; // rbook:// URL of the grid thumbnail
   path: string; // file path to the .epub file
   last_opened?: number; // timestamp when the book was last opened
+  progress?: number; // reading progress percentage (0-100)
 }
 
 export interface TocItem {